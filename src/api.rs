@@ -0,0 +1,421 @@
+use chia::{
+    bls::{PublicKey, Signature},
+    protocol::{Bytes32, Coin, SpendBundle},
+};
+use chia_wallet_sdk::{
+    coinset::ChiaRpcClient,
+    driver::{Cat, MedievalVault, SpendContext, create_security_coin, spend_security_coin},
+    types::Conditions,
+};
+
+use crate::{
+    Funding, IssuanceOutput, Network, PendingVaultSpend, RcliError, RpcClient, VaultSpendOptions,
+    WalletSigner, get_cat_from_coin_record, get_rcat_asset_id, get_rcat_hidden_puzzle_hash,
+    get_revoked_amount, push_spend_bundle, select_vault_signers, sign_pending_vault_spend,
+    spend_issuance, spend_melt, spend_revocation, spend_vault_launch, sync_vault,
+    wait_for_confirmation,
+};
+
+// Progress reported by `RcatAuthority` (which never prints to stdout)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RcatEvent {
+    VaultSynced {
+        vault_coin_id: Bytes32,
+    },
+    CoinParsed {
+        coin_id: Bytes32,
+    },
+    Funded {
+        coin_id: Bytes32,
+    },
+    Signed {
+        missing_signers: usize,
+    },
+    Submitted {
+        transaction_id: Bytes32,
+        status: String,
+    },
+    Confirmed {
+        transaction_id: Bytes32,
+    },
+}
+
+type RcatEventHandler = Box<dyn Fn(&RcatEvent) + Send + Sync>;
+
+// Builds, signs and submits the transactions of the rCAT issued by a vault
+// Vault spends are returned unsigned, so they can also be signed by other
+//  members (see `PendingVaultSpend`)
+pub struct RcatAuthority {
+    pub launcher_id: Bytes32,
+    pub nonce: u64,
+    pub network: Network,
+    client: RpcClient,
+    wallet: WalletSigner,
+    signers: Option<Vec<PublicKey>>,
+    on_event: Option<RcatEventHandler>,
+}
+
+impl RcatAuthority {
+    pub fn new(
+        client: RpcClient,
+        wallet: WalletSigner,
+        launcher_id: Bytes32,
+        nonce: u64,
+        network: Network,
+    ) -> Self {
+        Self {
+            launcher_id,
+            nonce,
+            network,
+            client,
+            wallet,
+            signers: None,
+            on_event: None,
+        }
+    }
+
+    // Vault spends will be signed by these members; defaults to the ones the
+    //  wallet controls
+    pub fn with_signers(mut self, signers: Vec<PublicKey>) -> Self {
+        self.signers = Some(signers);
+        self
+    }
+
+    pub fn with_event_handler<F>(mut self, on_event: F) -> Self
+    where
+        F: Fn(&RcatEvent) + Send + Sync + 'static,
+    {
+        self.on_event = Some(Box::new(on_event));
+        self
+    }
+
+    // Builds a (signed) bundle that launches an m-of-n vault
+    // Returns the vault's launcher id and the bundle
+    pub async fn launch_vault(
+        wallet: &WalletSigner,
+        m: usize,
+        public_keys: Vec<PublicKey>,
+        fee: u64,
+        network: &Network,
+    ) -> Result<(Bytes32, SpendBundle), RcliError> {
+        if m == 0 || m > public_keys.len() {
+            return Err(RcliError::Custom(format!(
+                "Invalid threshold: {}-of-{}",
+                m,
+                public_keys.len()
+            )));
+        }
+
+        let mut ctx = SpendContext::new();
+        let funding = wallet.fund(&mut ctx, 1, fee, true).await?;
+        let (launcher_id, _, security_coin_sig) =
+            spend_vault_launch(&mut ctx, funding.coin, m, public_keys, network)?;
+
+        Ok((
+            launcher_id,
+            funding.take(SpendBundle::new(ctx.take(), security_coin_sig)),
+        ))
+    }
+
+    pub fn asset_id(&self) -> Bytes32 {
+        get_rcat_asset_id(self.launcher_id, self.nonce)
+    }
+
+    pub fn hidden_puzzle_hash(&self) -> Bytes32 {
+        get_rcat_hidden_puzzle_hash(self.launcher_id)
+    }
+
+    // Builds an issuance of a single eve rCAT that creates the given outputs
+    pub async fn issue(
+        &self,
+        outputs: Vec<IssuanceOutput>,
+        fee: u64,
+    ) -> Result<PendingVaultSpend, RcliError> {
+        let mut ctx = SpendContext::new();
+        let vault = self.sync_vault(&mut ctx).await?;
+        let signers = self.select_signers(&vault).await?;
+        let vault_coin_id = vault.coin.coin_id();
+
+        let cat_amount = outputs.iter().map(|o| o.amount).sum::<u64>();
+        let funding = self.fund(&mut ctx, cat_amount, fee, true).await?;
+
+        let (security_coin, security_coin_sig) = spend_issuance(
+            &mut ctx,
+            self.launcher_id,
+            self.nonce,
+            &self.network,
+            vault,
+            &signers,
+            funding.coin,
+            outputs,
+        )?;
+
+        Ok(self.pending_vault_spend(
+            signers,
+            vault_coin_id,
+            security_coin,
+            funding.take(SpendBundle::new(ctx.take(), security_coin_sig)),
+        ))
+    }
+
+    // Builds a revocation of the given coins; holders keep `percentage` of each
+    //  coin and the rest is melted (`burn`) or sent to `destination`
+    pub async fn revoke(
+        &self,
+        coin_ids: Vec<Bytes32>,
+        percentage: u8,
+        burn: bool,
+        destination: Bytes32,
+        fee: u64,
+    ) -> Result<PendingVaultSpend, RcliError> {
+        if percentage > 100 {
+            return Err(RcliError::Custom(format!(
+                "Invalid percentage: {} (must be at most 100)",
+                percentage
+            )));
+        }
+
+        let mut ctx = SpendContext::new();
+        let cats = self.get_rcats(&mut ctx, coin_ids).await?;
+
+        let total_revoked_amount = cats
+            .iter()
+            .map(|cat| get_revoked_amount(cat.coin.amount, percentage))
+            .sum::<u64>();
+        if burn && total_revoked_amount == 0 {
+            return Err(RcliError::Custom("Nothing to burn".to_string()));
+        }
+
+        let vault = self.sync_vault(&mut ctx).await?;
+        let signers = self.select_signers(&vault).await?;
+        let vault_coin_id = vault.coin.coin_id();
+
+        let funding = self.fund(&mut ctx, 1, fee, false).await?;
+        let (security_coin, security_coin_sig) = spend_asserting_security_coin(
+            &mut ctx,
+            funding.coin,
+            cats[0].coin.coin_id(),
+            &self.network,
+        )?;
+
+        spend_revocation(
+            &mut ctx,
+            self.launcher_id,
+            self.nonce,
+            &self.network,
+            vault,
+            &signers,
+            cats,
+            percentage,
+            burn,
+            destination,
+        )?;
+
+        Ok(self.pending_vault_spend(
+            signers,
+            vault_coin_id,
+            security_coin,
+            funding.take(SpendBundle::new(ctx.take(), security_coin_sig)),
+        ))
+    }
+
+    // Builds a melt of `amount` from the given coins; whatever is not melted
+    //  goes back to the coins' owners
+    pub async fn melt(
+        &self,
+        coin_ids: Vec<Bytes32>,
+        amount: u64,
+        fee: u64,
+    ) -> Result<PendingVaultSpend, RcliError> {
+        let mut ctx = SpendContext::new();
+        let cats = self.get_rcats(&mut ctx, coin_ids).await?;
+
+        let total_cat_amount = cats.iter().map(|cat| cat.coin.amount).sum::<u64>();
+        if amount == 0 || amount > total_cat_amount {
+            return Err(RcliError::Custom(format!(
+                "Invalid melt amount: {} (selected coins hold {})",
+                amount, total_cat_amount
+            )));
+        }
+
+        let vault = self.sync_vault(&mut ctx).await?;
+        let signers = self.select_signers(&vault).await?;
+        let vault_coin_id = vault.coin.coin_id();
+
+        let funding = self.fund(&mut ctx, 1, fee, false).await?;
+        let (security_coin, security_coin_sig) = spend_asserting_security_coin(
+            &mut ctx,
+            funding.coin,
+            cats[0].coin.coin_id(),
+            &self.network,
+        )?;
+
+        spend_melt(
+            &mut ctx,
+            self.launcher_id,
+            self.nonce,
+            &self.network,
+            vault,
+            &signers,
+            cats,
+            amount,
+        )?;
+
+        Ok(self.pending_vault_spend(
+            signers,
+            vault_coin_id,
+            security_coin,
+            funding.take(SpendBundle::new(ctx.take(), security_coin_sig)),
+        ))
+    }
+
+    // Adds the wallet's signature to the vault spend; once no signers are
+    //  missing, `pending.signed_spend_bundle()` can be submitted
+    pub async fn sign(&self, pending: &mut PendingVaultSpend) -> Result<(), RcliError> {
+        sign_pending_vault_spend(&self.wallet, pending).await?;
+
+        self.emit(RcatEvent::Signed {
+            missing_signers: pending.missing_signers().len(),
+        });
+        Ok(())
+    }
+
+    // Pushes the bundle and waits until it is confirmed; returns its id
+    pub async fn submit(&self, sb: SpendBundle) -> Result<Bytes32, RcliError> {
+        let transaction_id = sb.name();
+        let Some(spent_coin_id) = sb.coin_spends.first().map(|cs| cs.coin.coin_id()) else {
+            return Err(RcliError::Custom("Spend bundle is empty".to_string()));
+        };
+
+        let status = push_spend_bundle(&self.client, sb).await?;
+        self.emit(RcatEvent::Submitted {
+            transaction_id,
+            status,
+        });
+
+        wait_for_confirmation(&self.client, spent_coin_id).await?;
+        self.emit(RcatEvent::Confirmed { transaction_id });
+
+        Ok(transaction_id)
+    }
+
+    fn emit(&self, event: RcatEvent) {
+        if let Some(on_event) = &self.on_event {
+            on_event(&event);
+        }
+    }
+
+    async fn sync_vault(&self, ctx: &mut SpendContext) -> Result<MedievalVault, RcliError> {
+        let vault = sync_vault(&self.client, ctx, self.launcher_id).await?;
+
+        self.emit(RcatEvent::VaultSynced {
+            vault_coin_id: vault.coin.coin_id(),
+        });
+        Ok(vault)
+    }
+
+    async fn select_signers(&self, vault: &MedievalVault) -> Result<Vec<PublicKey>, RcliError> {
+        let options = VaultSpendOptions {
+            signers: self.signers.clone(),
+            ..Default::default()
+        };
+
+        select_vault_signers(&self.wallet, vault, &options).await
+    }
+
+    async fn fund(
+        &self,
+        ctx: &mut SpendContext,
+        amount: u64,
+        fee: u64,
+        auto_import: bool,
+    ) -> Result<Funding, RcliError> {
+        let funding = self.wallet.fund(ctx, amount, fee, auto_import).await?;
+
+        self.emit(RcatEvent::Funded {
+            coin_id: funding.coin.coin_id(),
+        });
+        Ok(funding)
+    }
+
+    // Fetches and parses unspent rCATs issued by this authority
+    async fn get_rcats(
+        &self,
+        ctx: &mut SpendContext,
+        coin_ids: Vec<Bytes32>,
+    ) -> Result<Vec<Cat>, RcliError> {
+        let coin_ids_len = coin_ids.len();
+        let Some(coin_records) = self
+            .client
+            .get_coin_records_by_names(coin_ids, None, None, Some(true))
+            .await?
+            .coin_records
+        else {
+            return Err(RcliError::Custom("Error fetching coin records".to_string()));
+        };
+        if coin_records.is_empty() || coin_records.len() != coin_ids_len {
+            return Err(RcliError::Custom(
+                "Could not find one or more rCAT coins on-chain".to_string(),
+            ));
+        }
+
+        let mut cats = Vec::with_capacity(coin_records.len());
+        for coin_record in coin_records {
+            let coin_id = coin_record.coin.coin_id();
+            if coin_record.spent {
+                return Err(RcliError::CoinAlreadySpent(coin_id));
+            }
+
+            let cat = get_cat_from_coin_record(&self.client, ctx, &coin_record).await?;
+            if cat.info.asset_id != self.asset_id()
+                || cat.info.hidden_puzzle_hash != Some(self.hidden_puzzle_hash())
+            {
+                return Err(RcliError::WrongAssetId(coin_id));
+            }
+
+            self.emit(RcatEvent::CoinParsed { coin_id });
+            cats.push(cat);
+        }
+
+        Ok(cats)
+    }
+
+    fn pending_vault_spend(
+        &self,
+        signers: Vec<PublicKey>,
+        vault_coin_id: Bytes32,
+        security_coin: Coin,
+        spend_bundle: SpendBundle,
+    ) -> PendingVaultSpend {
+        PendingVaultSpend {
+            launcher_id: self.launcher_id,
+            network: self.network.clone(),
+            signers,
+            vault_coin_id,
+            confirmation_coin_id: security_coin.coin_id(),
+            spend_bundle,
+            signatures: vec![],
+        }
+    }
+}
+
+// Creates a security coin from the funding coin and spends it, asserting that
+//  the given coin is spent in the same bundle
+fn spend_asserting_security_coin(
+    ctx: &mut SpendContext,
+    funding_coin: Coin,
+    coin_id: Bytes32,
+    network: &Network,
+) -> Result<(Coin, Signature), RcliError> {
+    let (security_sk, security_coin) = create_security_coin(ctx, funding_coin)?;
+
+    let security_coin_sig = spend_security_coin(
+        ctx,
+        security_coin,
+        Conditions::new().assert_concurrent_spend(coin_id),
+        &security_sk,
+        &network.constants(),
+    )?;
+
+    Ok((security_coin, security_coin_sig))
+}
@@ -1,15 +1,53 @@
+mod config;
+mod derive;
+mod dry_run;
+mod error;
 mod generate_send_message_bundle;
+mod holders;
+mod inspect_coin;
 mod issue;
+mod keyfile;
 mod launch_vault;
+mod melt;
+mod multisig;
+mod network;
+mod nonces;
+mod output;
 mod ping;
+mod revocation_report;
 mod revoke;
 mod revoke_bulk;
+mod revoke_bulk_state;
+mod rpc_client;
+mod scan_asset;
 mod shared;
+mod signer;
+mod vault_info;
+mod vault_rotate;
 
+pub use config::*;
+pub use derive::*;
+pub use dry_run::*;
+pub use error::*;
 pub use generate_send_message_bundle::*;
+pub use holders::*;
+pub use inspect_coin::*;
 pub use issue::*;
+pub use keyfile::*;
 pub use launch_vault::*;
+pub use melt::*;
+pub use multisig::*;
+pub use network::*;
+pub use nonces::*;
+pub use output::*;
 pub use ping::*;
+pub use revocation_report::*;
 pub use revoke::*;
 pub use revoke_bulk::*;
+pub use revoke_bulk_state::*;
+pub use rpc_client::*;
+pub use scan_asset::*;
 pub use shared::*;
+pub use signer::*;
+pub use vault_info::*;
+pub use vault_rotate::*;
@@ -0,0 +1,329 @@
+use chia::protocol::Bytes32;
+use serde::Deserialize;
+use slot_machine::hex_string_to_bytes32;
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, IsTerminal, Write},
+    path::PathBuf,
+};
+use toml_edit::{DocumentMut, Item, Table, table, value};
+
+use crate::{ConstantsOverrides, Network, NetworkKind, RcliError, is_json_output};
+
+// If set, the config file is read from this path instead of ~/.config/rcli/config.toml
+pub const CONFIG_ENV_VAR: &str = "RCLI_CONFIG";
+
+pub fn get_config_path() -> PathBuf {
+    if let Ok(path) = env::var(CONFIG_ENV_VAR) {
+        return PathBuf::from(path);
+    }
+
+    let config_dir = env::var("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(env::var("HOME").unwrap_or_default()).join(".config"));
+    config_dir.join("rcli/config.toml")
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+// Settings of one issuer (vault + rCATs); command-line options take precedence,
+//  but the launcher id, nonce and network must match the profile's if it sets them
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub launcher_id: Option<String>,
+    // The first nonce is the default; other nonces are rejected
+    #[serde(default)]
+    pub nonces: Vec<u64>,
+    pub network: Option<NetworkKind>,
+    pub genesis_challenge: Option<String>,
+    pub agg_sig_me_data: Option<String>,
+    pub address_prefix: Option<String>,
+    pub rpc_url: Option<String>,
+    pub constants_base: Option<NetworkKind>,
+    // Consensus constants of a custom network that differ from its base's
+    #[serde(default)]
+    pub constants: ConstantsOverrides,
+    pub fee: Option<String>,
+    pub destination: Option<String>,
+    #[serde(default)]
+    pub exclude_addresses: Vec<String>,
+}
+
+impl Config {
+    // A missing config file is the same as an empty one
+    pub fn load() -> Result<Self, RcliError> {
+        let path = get_config_path();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        toml::from_str(&contents).map_err(|err| {
+            RcliError::Custom(format!("Invalid config file '{}': {err}", path.display()))
+        })
+    }
+
+    pub fn profile(&self, name: &str) -> Result<Profile, RcliError> {
+        self.profiles.get(name).cloned().ok_or_else(|| {
+            RcliError::Custom(format!(
+                "Profile '{}' not found in '{}'",
+                name,
+                get_config_path().display()
+            ))
+        })
+    }
+}
+
+// Without a profile name, all settings come from the command line
+pub fn load_profile(name: Option<String>) -> Result<Profile, RcliError> {
+    match name {
+        Some(name) => Config::load()?.profile(&name),
+        None => Ok(Profile::default()),
+    }
+}
+
+impl Profile {
+    pub fn launcher_id(&self, launcher_id: Option<String>) -> Result<String, RcliError> {
+        match (launcher_id, &self.launcher_id) {
+            (Some(launcher_id), Some(profile_launcher_id)) => {
+                if hex_string_to_bytes32(&launcher_id)?
+                    != hex_string_to_bytes32(profile_launcher_id)?
+                {
+                    return Err(RcliError::Custom(format!(
+                        "Launcher id {} does not match the profile's launcher id {}",
+                        launcher_id, profile_launcher_id
+                    )));
+                }
+
+                Ok(launcher_id)
+            }
+            (Some(launcher_id), None) => Ok(launcher_id),
+            (None, Some(profile_launcher_id)) => Ok(profile_launcher_id.clone()),
+            (None, None) => Err(RcliError::Custom(
+                "--launcher-id is required (or use a --profile that sets launcher_id)".to_string(),
+            )),
+        }
+    }
+
+    pub fn nonce(&self, nonce: Option<u64>) -> Result<u64, RcliError> {
+        let Some(nonce) = nonce else {
+            return Ok(self.nonces.first().copied().unwrap_or(0));
+        };
+
+        if !self.nonces.is_empty() && !self.nonces.contains(&nonce) {
+            return Err(RcliError::Custom(format!(
+                "Nonce {} is not one of the profile's nonces ({:?})",
+                nonce, self.nonces
+            )));
+        }
+
+        Ok(nonce)
+    }
+
+    pub fn fee(&self, fee: Option<String>, default: &str) -> String {
+        fee.or_else(|| self.fee.clone())
+            .unwrap_or_else(|| default.to_string())
+    }
+
+    // Burning ignores the profile's destination
+    pub fn destination(&self, destination: Option<String>, burn: bool) -> Option<String> {
+        if burn {
+            return destination;
+        }
+
+        destination.or_else(|| self.destination.clone())
+    }
+
+    pub fn exclude_addresses(&self, exclude_addresses: Option<String>) -> Option<String> {
+        exclude_addresses.or_else(|| {
+            (!self.exclude_addresses.is_empty()).then(|| self.exclude_addresses.join(","))
+        })
+    }
+
+    pub fn network(
+        &self,
+        kind: Option<NetworkKind>,
+        genesis_challenge: Option<String>,
+        agg_sig_me_data: Option<String>,
+        address_prefix: Option<String>,
+        rpc_url: Option<String>,
+        constants_base: Option<NetworkKind>,
+    ) -> Result<Network, RcliError> {
+        if let (Some(kind), Some(profile_kind)) = (kind, self.network)
+            && kind != profile_kind
+        {
+            return Err(RcliError::Custom(format!(
+                "Network {:?} does not match the profile's network ({:?})",
+                kind, profile_kind
+            )));
+        }
+
+        Network::parse(
+            kind.or(self.network).unwrap_or_default(),
+            genesis_challenge.or_else(|| self.genesis_challenge.clone()),
+            agg_sig_me_data.or_else(|| self.agg_sig_me_data.clone()),
+            address_prefix.or_else(|| self.address_prefix.clone()),
+            rpc_url.or_else(|| self.rpc_url.clone()),
+            constants_base.or(self.constants_base),
+            self.constants,
+        )
+    }
+}
+
+// Asks for a profile name to save a new vault to (only when run interactively)
+pub fn prompt_profile_name() -> Result<Option<String>, RcliError> {
+    if is_json_output() || !io::stdin().is_terminal() {
+        return Ok(None);
+    }
+
+    print!("Save the launcher id to a profile? Enter a profile name (leave empty to skip): ");
+    io::stdout().flush()?;
+
+    let mut name = String::new();
+    io::stdin().read_line(&mut name)?;
+    let name = name.trim();
+
+    Ok((!name.is_empty()).then(|| name.to_string()))
+}
+
+// Checks a new vault can be saved to the given profile - done before launching,
+//  so a failed save can't make a retried launch create a second vault
+pub fn check_profile_can_save(name: &str, network: &Network) -> Result<(), RcliError> {
+    let mut doc = load_config_document()?;
+    get_profile_table(&mut doc, name, network)?;
+
+    Ok(())
+}
+
+// Saves the launcher id and network of a new vault to the given profile, creating
+//  the profile (and config file) if needed; everything else in the file is kept
+pub fn save_launcher_id_to_profile(
+    name: &str,
+    launcher_id: Bytes32,
+    network: &Network,
+) -> Result<PathBuf, RcliError> {
+    let path = get_config_path();
+    let mut doc = load_config_document()?;
+    let profile = get_profile_table(&mut doc, name, network)?;
+
+    profile.insert(
+        "launcher_id",
+        value(format!("0x{}", hex::encode(launcher_id))),
+    );
+    profile.insert("network", value(network.name()));
+    if network.kind == NetworkKind::Custom {
+        profile.insert(
+            "genesis_challenge",
+            value(format!("0x{}", hex::encode(network.genesis_challenge))),
+        );
+        if network.agg_sig_me_additional_data != network.genesis_challenge {
+            profile.insert(
+                "agg_sig_me_data",
+                value(format!(
+                    "0x{}",
+                    hex::encode(network.agg_sig_me_additional_data)
+                )),
+            );
+        }
+        profile.insert("address_prefix", value(network.address_prefix.clone()));
+        profile.insert(
+            "constants_base",
+            value(if network.constants_base == NetworkKind::Mainnet {
+                "mainnet"
+            } else {
+                "testnet11"
+            }),
+        );
+
+        let overrides = network.constants_overrides;
+        if !overrides.is_empty() {
+            let mut constants = Table::new();
+            if let Some(max_block_cost_clvm) = overrides.max_block_cost_clvm {
+                constants.insert("max_block_cost_clvm", value(max_block_cost_clvm as i64));
+            }
+            if let Some(cost_per_byte) = overrides.cost_per_byte {
+                constants.insert("cost_per_byte", value(cost_per_byte as i64));
+            }
+            if let Some(hard_fork_height) = overrides.hard_fork_height {
+                constants.insert("hard_fork_height", value(hard_fork_height as i64));
+            }
+            profile.insert("constants", Item::Table(constants));
+        }
+    }
+    if let Some(rpc_url) = &network.rpc_url {
+        profile.insert("rpc_url", value(rpc_url.clone()));
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&path, doc.to_string())?;
+
+    Ok(path)
+}
+
+fn invalid_config(err: String) -> RcliError {
+    RcliError::Custom(format!(
+        "Invalid config file '{}': {err}",
+        get_config_path().display()
+    ))
+}
+
+fn load_config_document() -> Result<DocumentMut, RcliError> {
+    let path = get_config_path();
+    let contents = if path.exists() {
+        fs::read_to_string(&path)?
+    } else {
+        String::new()
+    };
+
+    contents
+        .parse::<DocumentMut>()
+        .map_err(|err| invalid_config(err.to_string()))
+}
+
+// Returns the profile's table (creating it if needed), as long as the profile
+//  has no launcher id yet and doesn't belong to another network
+fn get_profile_table<'a>(
+    doc: &'a mut DocumentMut,
+    name: &str,
+    network: &Network,
+) -> Result<&'a mut Table, RcliError> {
+    if !doc.contains_key("profiles") {
+        let mut profiles = Table::new();
+        profiles.set_implicit(true);
+        doc.insert("profiles", Item::Table(profiles));
+    }
+    let profile = doc["profiles"]
+        .as_table_mut()
+        .ok_or_else(|| invalid_config("'profiles' is not a table".to_string()))?
+        .entry(name)
+        .or_insert(table())
+        .as_table_mut()
+        .ok_or_else(|| invalid_config(format!("profile '{}' is not a table", name)))?;
+
+    if let Some(existing) = profile.get("launcher_id").and_then(|item| item.as_str()) {
+        return Err(RcliError::Custom(format!(
+            "Profile '{}' already has a launcher id ({})",
+            name, existing
+        )));
+    }
+    if let Some(existing) = profile.get("network").and_then(|item| item.as_str())
+        && existing != network.name()
+    {
+        return Err(RcliError::Custom(format!(
+            "Network {} does not match the profile's network ({})",
+            network.name(),
+            existing
+        )));
+    }
+
+    Ok(profile)
+}
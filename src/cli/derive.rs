@@ -0,0 +1,56 @@
+use chia_wallet_sdk::utils::Address;
+use slot_machine::hex_string_to_bytes32;
+
+use crate::{
+    Network, RcliError, get_rcat_asset_id, get_rcat_hidden_puzzle_hash, get_rcat_puzzle_hash,
+    get_singleton_struct_hash, parse_destination, progress, record_puzzle_hash, record_rcat,
+    record_singleton_struct_hash,
+};
+
+// Computes the rCAT's identifiers from the launcher id and nonce - fully offline
+pub fn cli_derive(
+    launcher_id_str: String,
+    nonce: u64,
+    inner: Option<String>,
+    network: &Network,
+) -> Result<(), RcliError> {
+    let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+    record_rcat(launcher_id, nonce);
+
+    let singleton_struct_hash = get_singleton_struct_hash(launcher_id);
+    record_singleton_struct_hash(singleton_struct_hash);
+
+    progress!("Launcher id: 0x{}", hex::encode(launcher_id));
+    progress!(
+        "rCAT asset id (nonce {}): 0x{}",
+        nonce,
+        hex::encode(get_rcat_asset_id(launcher_id, nonce))
+    );
+    progress!(
+        "Hidden puzzle hash: 0x{}",
+        hex::encode(get_rcat_hidden_puzzle_hash(launcher_id))
+    );
+    progress!(
+        "Singleton struct hash: 0x{}",
+        hex::encode(singleton_struct_hash)
+    );
+
+    let Some(inner) = inner else {
+        return Ok(());
+    };
+
+    for inner in inner.split(',') {
+        let inner_puzzle_hash = parse_destination(inner.trim(), launcher_id, network)?;
+        let puzzle_hash = get_rcat_puzzle_hash(launcher_id, nonce, inner_puzzle_hash);
+        record_puzzle_hash(puzzle_hash);
+
+        progress!(
+            "rCAT puzzle hash for {} (inner puzzle hash 0x{}): 0x{}",
+            Address::new(inner_puzzle_hash, network.prefix()).encode()?,
+            hex::encode(inner_puzzle_hash),
+            hex::encode(puzzle_hash)
+        );
+    }
+
+    Ok(())
+}
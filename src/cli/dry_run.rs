@@ -0,0 +1,108 @@
+use chia::{
+    consensus::spendbundle_validation::validate_clvm_and_signature,
+    protocol::{Bytes32, Coin, SpendBundle},
+};
+use chia_wallet_sdk::{driver::SpendContext, types::Condition};
+use clvmr::NodePtr;
+
+use crate::{Network, RcliError, get_spend_conditions, progress, record_bundle_id, record_status};
+
+// Runs every coin spend in the bundle through clvm and checks the aggregated
+//  signature against the AGG_SIG conditions - nothing is submitted
+pub fn simulate_spend_bundle(sb: &SpendBundle, network: &Network) -> Result<(), RcliError> {
+    let constants = &network.constants();
+
+    progress!("Dry run: simulating bundle locally...");
+    record_bundle_id(sb.name());
+    let (conditions, _, _) = validate_clvm_and_signature(
+        sb,
+        constants.max_block_cost_clvm,
+        constants,
+        constants.hard_fork_height,
+    )
+    .map_err(|err| RcliError::Custom(format!("Bundle failed validation: {:?}", err)))?;
+
+    let mut ctx = SpendContext::new();
+    for coin_spend in &sb.coin_spends {
+        progress!(
+            "Spend of coin 0x{} ({} mojos, puzzle hash {}):",
+            hex::encode(coin_spend.coin.coin_id()),
+            coin_spend.coin.amount,
+            hex::encode(coin_spend.coin.puzzle_hash)
+        );
+
+        for condition in get_spend_conditions(&mut ctx, coin_spend)? {
+            progress!("  {}", describe_condition(&ctx, &condition));
+        }
+    }
+
+    progress!("Created coins:");
+    for spend in &conditions.spends {
+        for (puzzle_hash, amount, _) in &spend.create_coin {
+            let coin_id = Coin::new(spend.coin_id, *puzzle_hash, *amount).coin_id();
+            progress!(
+                "  0x{} (puzzle hash {}, {} mojos)",
+                hex::encode(coin_id),
+                hex::encode(puzzle_hash),
+                amount
+            );
+        }
+    }
+
+    progress!("Signature: valid");
+    progress!("CLVM cost: {}", conditions.cost);
+    progress!(
+        "Fee: {:.12} XCH",
+        (conditions.removal_amount - conditions.addition_amount) as f64 / 1_000_000_000_000.0
+    );
+    progress!("Dry run complete; the transaction was NOT submitted.");
+    record_status("simulated");
+
+    Ok(())
+}
+
+fn describe_condition(ctx: &SpendContext, condition: &Condition<NodePtr>) -> String {
+    match condition {
+        Condition::CreateCoin(create_coin) => format!(
+            "CREATE_COIN {} {}",
+            hex::encode(create_coin.puzzle_hash),
+            create_coin.amount
+        ),
+        Condition::SendMessage(send_message) => {
+            let receiver = send_message
+                .data
+                .first()
+                .and_then(|ptr| ctx.extract::<Bytes32>(*ptr).ok())
+                .map(|receiver| format!("0x{}", hex::encode(receiver)))
+                .unwrap_or_else(|| "?".to_string());
+
+            format!(
+                "SEND_MESSAGE mode {} to {}: {}",
+                send_message.mode,
+                receiver,
+                hex::encode(&send_message.message)
+            )
+        }
+        Condition::ReceiveMessage(receive_message) => format!(
+            "RECEIVE_MESSAGE mode {}: {}",
+            receive_message.mode,
+            hex::encode(&receive_message.message)
+        ),
+        Condition::AssertConcurrentSpend(assert) => {
+            format!("ASSERT_CONCURRENT_SPEND 0x{}", hex::encode(assert.coin_id))
+        }
+        Condition::AggSigMe(agg_sig) => format!(
+            "AGG_SIG_ME {} {}",
+            hex::encode(agg_sig.public_key.to_bytes()),
+            hex::encode(&agg_sig.message)
+        ),
+        Condition::AggSigUnsafe(agg_sig) => format!(
+            "AGG_SIG_UNSAFE {} {}",
+            hex::encode(agg_sig.public_key.to_bytes()),
+            hex::encode(&agg_sig.message)
+        ),
+        Condition::ReserveFee(reserve_fee) => format!("RESERVE_FEE {}", reserve_fee.amount),
+        Condition::RunCatTail(_) => "RUN_CAT_TAIL".to_string(),
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,58 @@
+use chia::protocol::Bytes32;
+use slot_machine::CliError;
+use thiserror::Error;
+
+// Process exit codes - see the 'Exit codes' section of the README
+pub const EXIT_CODE_OTHER: i32 = 1;
+pub const EXIT_CODE_WALLET_UNREACHABLE: i32 = 3;
+pub const EXIT_CODE_VAULT_NOT_FOUND: i32 = 4;
+pub const EXIT_CODE_COIN_ALREADY_SPENT: i32 = 5;
+pub const EXIT_CODE_WRONG_ASSET_ID: i32 = 6;
+pub const EXIT_CODE_INSUFFICIENT_COINS: i32 = 7;
+pub const EXIT_CODE_PUSH_REJECTED: i32 = 8;
+pub const EXIT_CODE_CONFIRMATION_TIMEOUT: i32 = 9;
+
+#[derive(Debug, Error)]
+pub enum RcliError {
+    #[error("Wallet unreachable: {0}")]
+    WalletUnreachable(String),
+    #[error("No vault found with launcher id 0x{}", hex::encode(.0))]
+    VaultNotFound(Bytes32),
+    #[error("Coin 0x{} has already been spent", hex::encode(.0))]
+    CoinAlreadySpent(Bytes32),
+    #[error("Coin 0x{} is not an rCAT of the expected asset id", hex::encode(.0))]
+    WrongAssetId(Bytes32),
+    #[error("Insufficient coins: {0}")]
+    InsufficientCoins(String),
+    #[error("Transaction rejected by the mempool: {0}")]
+    PushRejected(String),
+    #[error("Timed out waiting for coin 0x{} to be spent", hex::encode(.0))]
+    ConfirmationTimeout(Bytes32),
+    #[error("{0}")]
+    Custom(String),
+    #[error(transparent)]
+    Cli(CliError),
+}
+
+// Anything slot-machine's helpers can convert into a CliError (io, reqwest, hex, ...)
+//  also converts into an RcliError, so `?` keeps working everywhere
+impl<E: Into<CliError>> From<E> for RcliError {
+    fn from(err: E) -> Self {
+        Self::Cli(err.into())
+    }
+}
+
+impl RcliError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::WalletUnreachable(_) => EXIT_CODE_WALLET_UNREACHABLE,
+            Self::VaultNotFound(_) => EXIT_CODE_VAULT_NOT_FOUND,
+            Self::CoinAlreadySpent(_) => EXIT_CODE_COIN_ALREADY_SPENT,
+            Self::WrongAssetId(_) => EXIT_CODE_WRONG_ASSET_ID,
+            Self::InsufficientCoins(_) => EXIT_CODE_INSUFFICIENT_COINS,
+            Self::PushRejected(_) => EXIT_CODE_PUSH_REJECTED,
+            Self::ConfirmationTimeout(_) => EXIT_CODE_CONFIRMATION_TIMEOUT,
+            Self::Custom(_) | Self::Cli(_) => EXIT_CODE_OTHER,
+        }
+    }
+}
@@ -11,37 +11,34 @@ use chia_wallet_sdk::{
 };
 use clvm_traits::clvm_quote;
 use clvmr::NodePtr;
-use slot_machine::{
-    CliError, MultisigSingleton, SageClient, get_coinset_client, get_constants,
-    hex_string_to_bytes32, hex_string_to_signature, sync_multisig_singleton,
-};
+use slot_machine::hex_string_to_bytes32;
 
-use crate::get_first_address;
+use crate::{
+    Network, RcliError, WalletSigner, get_rpc_client, get_vault_signing_keys, progress,
+    record_file, record_launcher_id, sync_vault,
+};
 
 pub async fn cli_generate_send_message_bundle(
     launcher_id_str: String,
     message: u64,
     receiver_puzzle_hash_str: String,
     output_file: String,
-    testnet11: bool,
-) -> Result<(), CliError> {
+    network: &Network,
+) -> Result<(), RcliError> {
     let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+    record_launcher_id(launcher_id);
     let receiver_puzzle_hash = hex_string_to_bytes32(&receiver_puzzle_hash_str)?;
 
     let mut ctx = SpendContext::new();
-    let client = get_coinset_client(testnet11);
+    let client = get_rpc_client(network)?;
 
-    let (MultisigSingleton::Vault(vault), _) =
-        sync_multisig_singleton::<()>(&client, &mut ctx, launcher_id, None).await?
-    else {
-        return Err(CliError::Custom("Could not sync vault".to_string()));
-    };
+    let vault = sync_vault(&client, &mut ctx, launcher_id).await?;
 
-    println!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+    progress!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
 
     // Get wallet
-    let wallet = SageClient::new()?;
-    let user_layer = get_first_address(&wallet).await?;
+    let wallet = WalletSigner::new(network)?;
+    let signing_keys = get_vault_signing_keys(&wallet, &vault).await?;
     let singleton_struct_hash: Bytes32 = SingletonStruct::new(launcher_id).tree_hash().into();
     let p2_layer = P2DelegatedBySingletonLayer::new(singleton_struct_hash, 0);
 
@@ -91,25 +88,20 @@ pub async fn cli_generate_send_message_bundle(
         );
     vault.spend(
         &mut ctx,
-        &[user_layer.synthetic_key],
+        &signing_keys,
         vault_conditions,
-        get_constants(testnet11).genesis_challenge,
+        network.genesis_challenge,
     )?;
 
     // Sign vault spend using wallet
     let spends = ctx.take();
     let vault_spend = spends.last().unwrap().clone();
-    let vault_sig = hex_string_to_signature(
-        &wallet
-            .sign_coin_spends(vec![vault_spend], false, true)
-            .await?
-            .spend_bundle
-            .aggregated_signature,
-    )?;
+    let vault_sig = wallet.sign_coin_spends(vec![vault_spend]).await?;
 
     // Print final bundle
     print_spend_bundle_to_file(spends, vault_sig, &output_file);
-    println!("Spend bundle saved to '{}'", output_file);
+    progress!("Spend bundle saved to '{}'", output_file);
+    record_file(&output_file);
 
     Ok(())
 }
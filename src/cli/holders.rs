@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+
+use chia::protocol::Bytes32;
+use chia_wallet_sdk::{
+    coinset::ChiaRpcClient,
+    driver::{Cat, Puzzle, SpendContext},
+};
+use csv::Writer;
+use slot_machine::{CliError, hex_string_to_bytes32};
+
+use crate::{
+    Network, RcliError, RpcClient, get_rcat_asset_id, get_rcat_hidden_puzzle_hash, get_rpc_client,
+    get_sent_messages, get_vault_spends, parse_eve_cat_tail, progress, record_coin_ids,
+    record_file, record_rcat,
+};
+
+pub async fn cli_holders(
+    launcher_id_str: String,
+    nonce: u64,
+    output_file: String,
+    network: &Network,
+) -> Result<(), RcliError> {
+    let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    record_rcat(launcher_id, nonce);
+    progress!(
+        "rCAT asset id (nonce {}): {:}",
+        nonce,
+        hex::encode(asset_id)
+    );
+
+    let mut ctx = SpendContext::new();
+    let client = get_rpc_client(network)?;
+
+    progress!("Crawling rCAT coins from the vault's issuances...");
+    let holders = get_rcat_holders(&client, &mut ctx, launcher_id, nonce).await?;
+
+    let mut writer = Writer::from_path(&output_file).map_err(CliError::Csv)?;
+    writer
+        .write_record(["coin_name", "puzzle_hash", "amount"])
+        .map_err(CliError::Csv)?;
+    for holder in &holders {
+        writer
+            .write_record([
+                format!("0x{}", hex::encode(holder.coin.coin_id())),
+                format!("0x{}", hex::encode(holder.p2_puzzle_hash())),
+                holder.coin.amount.to_string(),
+            ])
+            .map_err(CliError::Csv)?;
+    }
+    writer.flush()?;
+
+    progress!(
+        "Found {} unspent coins holding {:.3} rCATs; saved to '{}'",
+        holders.len(),
+        holders.iter().map(|cat| cat.coin.amount).sum::<u64>() as f64 / 1000.0,
+        output_file
+    );
+    record_coin_ids(holders.iter().map(|cat| cat.coin.coin_id()));
+    record_file(&output_file);
+
+    Ok(())
+}
+
+// Returns all unspent rCATs issued by the vault with the given nonce
+// Starts from the eve rCATs the vault approved and follows their children
+pub async fn get_rcat_holders(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    nonce: u64,
+) -> Result<Vec<Cat>, RcliError> {
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+
+    // Spent rCATs whose children still need to be parsed - all other coins
+    //  of this asset descend from the eve rCATs, which the vault approved
+    //  with an empty (delta = 0) message
+    let mut to_visit = Vec::new();
+    for (_, vault_spend) in get_vault_spends(client, launcher_id).await? {
+        for (_, receiver_coin_id) in get_sent_messages(ctx, &vault_spend)?
+            .into_iter()
+            .filter(|(message, _)| message.is_empty())
+        {
+            let Some(coin_record) = client
+                .get_coin_record_by_name(receiver_coin_id)
+                .await?
+                .coin_record
+            else {
+                continue;
+            };
+            let Some(eve_spend) = client
+                .get_puzzle_and_solution(receiver_coin_id, Some(coin_record.spent_block_index))
+                .await?
+                .coin_solution
+            else {
+                continue;
+            };
+
+            if parse_eve_cat_tail(ctx, &eve_spend)?
+                .is_some_and(|tail_args| tail_args.nonce == nonce)
+            {
+                to_visit.push(receiver_coin_id);
+            }
+        }
+    }
+
+    let mut visited = HashSet::new();
+    let mut holders = Vec::new();
+    while let Some(coin_id) = to_visit.pop() {
+        if !visited.insert(coin_id) {
+            continue;
+        }
+
+        let Some(coin_record) = client.get_coin_record_by_name(coin_id).await?.coin_record else {
+            continue;
+        };
+        let Some(coin_spend) = client
+            .get_puzzle_and_solution(coin_id, Some(coin_record.spent_block_index))
+            .await?
+            .coin_solution
+        else {
+            continue;
+        };
+
+        let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+        let puzzle = Puzzle::parse(ctx, puzzle);
+        let solution = ctx.alloc(&coin_spend.solution)?;
+        let Some(children) = Cat::parse_children(ctx, coin_spend.coin, puzzle, solution)? else {
+            continue;
+        };
+
+        let Some(child_records) = client
+            .get_coin_records_by_parent_ids(vec![coin_id], None, None, Some(true))
+            .await?
+            .coin_records
+        else {
+            continue;
+        };
+
+        for child in children {
+            if child.info.asset_id != asset_id
+                || child.info.hidden_puzzle_hash != Some(hidden_puzzle_hash)
+            {
+                continue;
+            }
+
+            let child_id = child.coin.coin_id();
+            let Some(child_record) = child_records
+                .iter()
+                .find(|record| record.coin.coin_id() == child_id)
+            else {
+                // Not confirmed yet
+                continue;
+            };
+
+            if child_record.spent {
+                to_visit.push(child_id);
+            } else {
+                holders.push(child);
+            }
+        }
+
+        if visited.len() % 100 == 0 {
+            progress!(
+                "Parsed {} spent coins; found {} unspent coins so far...",
+                visited.len(),
+                holders.len()
+            );
+        }
+    }
+
+    holders.sort_unstable_by(|a, b| b.coin.amount.cmp(&a.coin.amount));
+    Ok(holders)
+}
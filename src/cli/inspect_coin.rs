@@ -0,0 +1,131 @@
+use chia::protocol::Bytes32;
+use chia_wallet_sdk::{coinset::ChiaRpcClient, driver::SpendContext, utils::Address};
+use slot_machine::hex_string_to_bytes32;
+
+use crate::{
+    Config, Network, RcliError, get_rcat_asset_id, get_rcat_hidden_puzzle_hash, get_rpc_client,
+    get_vault_nonces, parse_cat_from_coin_record, progress, record_cat, record_coin_ids,
+    record_coin_state, record_rcat,
+};
+
+// Vaults coins are checked against: the given one and the ones in the config profiles
+//  (name, launcher id, nonces known from the profile)
+pub fn get_known_vaults(
+    launcher_id_str: Option<String>,
+) -> Result<Vec<(String, Bytes32, Vec<u64>)>, RcliError> {
+    let mut known_vaults = Vec::new();
+    if let Some(launcher_id_str) = launcher_id_str {
+        known_vaults.push((
+            "--launcher-id".to_string(),
+            hex_string_to_bytes32(&launcher_id_str)?,
+            vec![],
+        ));
+    }
+    let mut profiles = Config::load()?.profiles.into_iter().collect::<Vec<_>>();
+    profiles.sort_by(|a, b| a.0.cmp(&b.0));
+    for (name, profile) in profiles {
+        if let Some(launcher_id) = profile.launcher_id {
+            known_vaults.push((
+                format!("profile '{}'", name),
+                hex_string_to_bytes32(&launcher_id)?,
+                profile.nonces,
+            ));
+        }
+    }
+
+    Ok(known_vaults)
+}
+
+pub async fn cli_inspect_coin(
+    coin_id_str: String,
+    launcher_id_str: Option<String>,
+    network: &Network,
+) -> Result<(), RcliError> {
+    let coin_id = hex_string_to_bytes32(&coin_id_str)?;
+    record_coin_ids([coin_id]);
+
+    let known_vaults = get_known_vaults(launcher_id_str)?;
+
+    let client = get_rpc_client(network)?;
+    let Some(coin_record) = client.get_coin_record_by_name(coin_id).await?.coin_record else {
+        return Err(RcliError::Custom(format!(
+            "Could not find coin 0x{}",
+            hex::encode(coin_id)
+        )));
+    };
+
+    progress!("Coin 0x{}:", hex::encode(coin_id));
+    progress!("  Amount: {} mojos", coin_record.coin.amount);
+    progress!(
+        "  Puzzle hash: 0x{}",
+        hex::encode(coin_record.coin.puzzle_hash)
+    );
+    progress!(
+        "  Spent: {}",
+        if coin_record.spent {
+            format!("yes (block {})", coin_record.spent_block_index)
+        } else {
+            "no".to_string()
+        }
+    );
+
+    progress!("Parsing parent spend...");
+    let mut ctx = SpendContext::new();
+    let Some(cat) = parse_cat_from_coin_record(&client, &mut ctx, &coin_record).await? else {
+        progress!("Not a CAT.");
+        record_coin_state(coin_record.spent, None);
+        return Ok(());
+    };
+
+    let owner_address = Address::new(cat.info.p2_puzzle_hash, network.prefix()).encode()?;
+    record_cat(cat.info.asset_id, cat.info.hidden_puzzle_hash);
+    record_coin_state(coin_record.spent, Some(owner_address.clone()));
+
+    progress!("  Asset id: 0x{}", hex::encode(cat.info.asset_id));
+    progress!("  Owner: {}", owner_address);
+    let Some(hidden_puzzle_hash) = cat.info.hidden_puzzle_hash else {
+        progress!("A CAT without a revocation layer (not an rCAT).");
+        return Ok(());
+    };
+    progress!(
+        "  Hidden puzzle hash: 0x{}",
+        hex::encode(hidden_puzzle_hash)
+    );
+
+    let Some((vault_name, launcher_id, nonces)) =
+        known_vaults.into_iter().find(|(_, launcher_id, _)| {
+            get_rcat_hidden_puzzle_hash(*launcher_id) == hidden_puzzle_hash
+        })
+    else {
+        progress!("An rCAT, but not one revocable by a known vault.");
+        return Ok(());
+    };
+    progress!(
+        "Revocable by vault 0x{} ({})",
+        hex::encode(launcher_id),
+        vault_name
+    );
+
+    let mut nonce = nonces
+        .into_iter()
+        .find(|nonce| get_rcat_asset_id(launcher_id, *nonce) == cat.info.asset_id);
+    if nonce.is_none() {
+        progress!("Looking up the vault's issuances...");
+        nonce = get_vault_nonces(&client, &mut ctx, launcher_id)
+            .await?
+            .into_iter()
+            .find(|nonce| get_rcat_asset_id(launcher_id, *nonce) == cat.info.asset_id);
+    }
+
+    match nonce {
+        Some(nonce) => {
+            record_rcat(launcher_id, nonce);
+            progress!("An rCAT issued by the vault with nonce {}.", nonce);
+        }
+        None => progress!(
+            "The vault can revoke this coin, but its asset id does not match any rCAT issued by the vault."
+        ),
+    }
+
+    Ok(())
+}
@@ -1,96 +1,270 @@
-use chia::protocol::{Bytes, Bytes32, Coin, SpendBundle};
-use chia_puzzle_types::{Memos, cat::CatArgs, singleton::SingletonStruct};
+use chia::{
+    bls::{PublicKey, Signature},
+    protocol::{Bytes, Bytes32, Coin, SpendBundle},
+};
+use chia_puzzle_types::{Memos, cat::CatArgs};
+use chia_puzzles::SETTLEMENT_PAYMENT_HASH;
 use chia_wallet_sdk::{
     coinset::ChiaRpcClient,
     driver::{
-        Cat, CatInfo, CatSpend, Offer, SingletonInfo, Spend, SpendContext, create_security_coin,
-        decode_offer, spend_security_coin,
+        Cat, CatInfo, CatSpend, MedievalVault, SingletonInfo, Spend, SpendContext,
+        create_security_coin, spend_security_coin,
     },
     prelude::ToTreeHash,
-    types::{
-        Conditions, Mod,
-        puzzles::{P2DelegatedBySingletonLayerArgs, RevocationArgs},
-    },
+    types::{Conditions, Mod, puzzles::RevocationArgs},
     utils::Address,
 };
 use clvm_traits::clvm_quote;
 use clvmr::NodePtr;
-use slot_machine::{
-    CliError, MultisigSingleton, SageClient, assets_xch_only, get_coinset_client, get_constants,
-    get_prefix, hex_string_to_bytes32, hex_string_to_signature, no_assets, parse_amount,
-    sync_multisig_singleton, wait_for_coin,
-};
+use csv::ReaderBuilder;
+use serde::Deserialize;
+use slot_machine::{CliError, hex_string_to_bytes32, parse_amount};
+use std::{fs::File, path::Path};
 
 use crate::{
-    EverythingWithSingletonTailArgs, EverythingWithSingletonTailSolution, get_first_address,
+    EverythingWithSingletonTailArgs, EverythingWithSingletonTailSolution, Network,
+    PendingVaultSpend, RcliError, RpcClient, VaultSpendOptions, WalletSigner, complete_vault_spend,
+    get_first_address, get_rcat_asset_id, get_rcat_hidden_puzzle_hash, get_rpc_client,
+    get_spends_cost, progress, record_rcat, select_vault_signers, split_by_cost, sync_vault,
 };
 
+// Issuance transactions also contain the funding spends, so the (measured) cost
+//  of the vault, security coin and eve rCAT spends is kept a bit under the
+//  mempool's per-transaction limit (half of the block cost limit)
+pub const MAX_ISSUANCE_SPENDS_COST: u64 = 5_000_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IssuanceOutput {
+    pub puzzle_hash: Bytes32,
+    pub amount: u64,
+    pub memo: Option<Bytes>,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn cli_issue(
     launcher_id_str: String,
+    nonce: u64,
     cat_amount_str: String,
+    recipients: Option<String>,
     fee_str: String,
-    testnet11: bool,
-) -> Result<(), CliError> {
+    export: Option<String>,
+    signers: Option<String>,
+    dry_run: bool,
+    network: &Network,
+) -> Result<(), RcliError> {
     let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
     let cat_amount = parse_amount(&cat_amount_str, true)?;
     let fee = parse_amount(&fee_str, false)?;
+    let spend_options = VaultSpendOptions::parse(export, signers, dry_run)?;
 
-    let mut ctx = SpendContext::new();
-    let client = get_coinset_client(testnet11);
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    record_rcat(launcher_id, nonce);
+    progress!(
+        "rCAT asset id (nonce {}): {:}",
+        nonce,
+        hex::encode(asset_id)
+    );
 
-    let (MultisigSingleton::Vault(vault), _) =
-        sync_multisig_singleton::<()>(&client, &mut ctx, launcher_id, None).await?
-    else {
-        return Err(CliError::Custom("Could not sync vault".to_string()));
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+    progress!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
+
+    let client = get_rpc_client(network)?;
+    let wallet = WalletSigner::new(network)?;
+
+    let batches = if let Some(recipients) = recipients {
+        progress!("Getting recipients from '{}'...", recipients);
+        let recipients = load_recipients_csv(recipients)?;
+        progress!("Got {} recipients.", recipients.len());
+
+        let mut outputs = Vec::with_capacity(recipients.len());
+        for recipient in recipients {
+            outputs.push(recipient.to_issuance_output(network)?);
+        }
+
+        progress!("Measuring issuance cost...");
+        let mut ctx = SpendContext::new();
+        let vault = sync_vault(&client, &mut ctx, launcher_id).await?;
+        let signers = select_vault_signers(&wallet, &vault, &spend_options).await?;
+        split_issuance_outputs(
+            launcher_id,
+            nonce,
+            network,
+            &vault,
+            &signers,
+            outputs,
+            MAX_ISSUANCE_SPENDS_COST,
+        )?
+    } else {
+        // The whole amount goes to the user's address
+        let layer = get_first_address(&wallet).await?;
+        let user_ph: Bytes32 = layer.tree_hash().into();
+        progress!(
+            "Newly-created CATs will be sent to: {}",
+            Address::new(user_ph, network.prefix()).encode()?
+        );
+
+        vec![vec![IssuanceOutput {
+            puzzle_hash: user_ph,
+            amount: cat_amount,
+            memo: None,
+        }]]
     };
 
-    println!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+    let batch_count = batches.len();
+    if batch_count > 1 && spend_options.export.is_some() {
+        // Each transaction spends the vault coin created by the previous one
+        return Err(RcliError::Custom(format!(
+            "Issuance requires {} transactions, but only a single one can be exported",
+            batch_count
+        )));
+    }
+    if batch_count > 1 {
+        progress!(
+            "Issuance will be split into {} transactions (fee is paid for each one).",
+            batch_count
+        );
+    }
+    if batch_count > 1 && spend_options.dry_run {
+        progress!(
+            "Dry run: only the first transaction will be simulated, as the others spend the vault coin it creates."
+        );
+    }
 
-    let tail_args = EverythingWithSingletonTailArgs::new(launcher_id, 0);
-    let tail_ptr = ctx.curry(tail_args)?;
-    let asset_id: Bytes32 = tail_args.curry_tree_hash().into();
-    println!("rCAT asset id: {:}", hex::encode(asset_id));
-
-    let singleton_struct_hash: Bytes32 = SingletonStruct::new(launcher_id).tree_hash().into();
-    // let hidden_puzzle = P2DelegatedBySingletonLayer::new(singleton_struct_hash, 0);
-    let hidden_puzzle_hash: Bytes32 =
-        P2DelegatedBySingletonLayerArgs::curry_tree_hash(singleton_struct_hash, 0).into();
-    println!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
-
-    let wallet = SageClient::new()?;
-    let offer_resp = wallet
-        .make_offer(
-            no_assets(),
-            assets_xch_only(cat_amount),
+    for (i, outputs) in batches.into_iter().enumerate() {
+        progress!(
+            "Issuance {}/{}: {} outputs (total amount {:.3})...",
+            i + 1,
+            batch_count,
+            outputs.len(),
+            outputs.iter().map(|o| o.amount).sum::<u64>() as f64 / 1000.0
+        );
+
+        issue_rcats(
+            &client,
+            &wallet,
+            launcher_id,
+            nonce,
             fee,
-            None,
-            None,
-            true,
+            network,
+            outputs,
+            &spend_options,
         )
         .await?;
-    println!("Offer with id {} created.", offer_resp.offer_id);
+
+        if spend_options.dry_run {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Issues a single eve rCAT that creates the given outputs
+//  Waits until the issuance is confirmed (unless the vault spend is exported or simulated)
+#[allow(clippy::too_many_arguments)]
+pub async fn issue_rcats(
+    client: &RpcClient,
+    wallet: &WalletSigner,
+    launcher_id: Bytes32,
+    nonce: u64,
+    fee: u64,
+    network: &Network,
+    outputs: Vec<IssuanceOutput>,
+    spend_options: &VaultSpendOptions,
+) -> Result<(), RcliError> {
+    let mut ctx = SpendContext::new();
+
+    let vault = sync_vault(client, &mut ctx, launcher_id).await?;
+
+    progress!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+    let signers = select_vault_signers(wallet, &vault, spend_options).await?;
+    let vault_coin_id = vault.coin.coin_id();
+
+    let cat_amount = outputs.iter().map(|o| o.amount).sum::<u64>();
+    let funding = wallet.fund(&mut ctx, cat_amount, fee, true).await?;
+    progress!("{}", funding);
+
+    let (security_coin, security_coin_sig) = spend_issuance(
+        &mut ctx,
+        launcher_id,
+        nonce,
+        network,
+        vault,
+        &signers,
+        funding.coin,
+        outputs,
+    )?;
+
+    let offer_id = funding.offer_id();
+    // Assemble bundle - the vault spend still needs to be signed by its members
+    let sb = funding.take(SpendBundle::new(ctx.take(), security_coin_sig));
+
+    let result = complete_vault_spend(
+        client,
+        wallet,
+        PendingVaultSpend {
+            launcher_id,
+            network: network.clone(),
+            signers,
+            vault_coin_id,
+            confirmation_coin_id: security_coin.coin_id(),
+            spend_bundle: sb,
+            signatures: vec![],
+        },
+        spend_options,
+    )
+    .await;
+    if spend_options.dry_run {
+        wallet.cancel_funding(offer_id).await?;
+    }
+
+    result
+}
+
+// Spends the funding coin (via a security coin), the eve rCAT and the vault
+//  so that the eve rCAT creates the given outputs
+// Returns the security coin and its signature; the vault spend still needs
+//  to be signed by its members
+#[allow(clippy::too_many_arguments)]
+pub fn spend_issuance(
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: MedievalVault,
+    signers: &[PublicKey],
+    funding_coin: Coin,
+    outputs: Vec<IssuanceOutput>,
+) -> Result<(Coin, Signature), RcliError> {
+    let tail_args = EverythingWithSingletonTailArgs::new(launcher_id, nonce);
+    let tail_ptr = ctx.curry(tail_args)?;
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+
+    let cat_amount = outputs.iter().map(|o| o.amount).sum::<u64>();
 
     // Create security coin
-    let offer = Offer::from_spend_bundle(&mut ctx, &decode_offer(&offer_resp.offer)?)?;
-    let (security_sk, security_coin) =
-        create_security_coin(&mut ctx, offer.offered_coins().xch[0])?;
+    let (security_sk, security_coin) = create_security_coin(ctx, funding_coin)?;
 
     // Spend security coin, which will create the eve CAT and assert it's spent
     // To do that, we need the eve CAT's full puzzle hash
-    // The inner puzzle of the eve CAT just sends the whole amount to the user's address
-    let layer = get_first_address(&wallet).await?;
-    let user_ph: Bytes32 = layer.tree_hash().into();
-    println!(
-        "Newly-created CATs will be sent to: {}",
-        Address::new(user_ph, get_prefix(testnet11)).encode()?
-    );
-
+    // The inner puzzle of the eve CAT just creates the requested outputs
     let eve_cat_tail_solution = ctx.alloc(&EverythingWithSingletonTailSolution {
         singleton_inner_puzzle_hash: vault.info.inner_puzzle_hash().into(),
     })?;
-    let eve_cat_coin_conditions = Conditions::new()
-        .create_coin(user_ph, cat_amount, ctx.hint(user_ph)?)
-        .run_cat_tail(tail_ptr, eve_cat_tail_solution);
+    let mut eve_cat_coin_conditions = Conditions::new();
+    for output in outputs {
+        let memos = if let Some(memo) = output.memo {
+            Memos::Some(ctx.alloc(&vec![Bytes::new(output.puzzle_hash.to_vec()), memo])?)
+        } else {
+            ctx.hint(output.puzzle_hash)?
+        };
+
+        eve_cat_coin_conditions =
+            eve_cat_coin_conditions.create_coin(output.puzzle_hash, output.amount, memos);
+    }
+    let eve_cat_coin_conditions =
+        eve_cat_coin_conditions.run_cat_tail(tail_ptr, eve_cat_tail_solution);
     let eve_cat_inner_puzzle = ctx.alloc(&clvm_quote!(eve_cat_coin_conditions))?;
     let eve_cat_inner_puzzle_hash: Bytes32 = ctx.tree_hash(eve_cat_inner_puzzle).into();
 
@@ -105,18 +279,18 @@ pub async fn cli_issue(
     );
 
     let security_coin_sig = spend_security_coin(
-        &mut ctx,
+        ctx,
         security_coin,
         Conditions::new()
             .create_coin(eve_cat_full_puzzle_hash.into(), cat_amount, Memos::None)
             .assert_concurrent_spend(eve_cat_coin.coin_id()),
         &security_sk,
-        get_constants(testnet11),
+        &network.constants(),
     )?;
 
     // Spend eve CAT
     let _ = Cat::spend_all(
-        &mut ctx,
+        ctx,
         &[CatSpend::new(
             Cat::new(
                 eve_cat_coin,
@@ -143,34 +317,110 @@ pub async fn cli_issue(
             vault.coin.amount,
             vault_hint,
         );
-    vault.spend(
+    vault.spend(ctx, signers, conditions, network.genesis_challenge)?;
+
+    Ok((security_coin, security_coin_sig))
+}
+
+// Returns the CLVM cost of the vault, security coin and eve rCAT spends needed
+//  to issue the given outputs
+pub fn get_issuance_cost(
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: &MedievalVault,
+    signers: &[PublicKey],
+    outputs: &[IssuanceOutput],
+) -> Result<u64, RcliError> {
+    let mut ctx = SpendContext::new();
+
+    // Only the funding coin's amount matters; its own spends are not measured
+    let amount = outputs.iter().map(|o| o.amount).sum::<u64>();
+    let funding_coin = Coin::new(Bytes32::default(), SETTLEMENT_PAYMENT_HASH.into(), amount);
+    spend_issuance(
         &mut ctx,
-        &[layer.synthetic_key],
-        conditions,
-        get_constants(testnet11).genesis_challenge,
+        launcher_id,
+        nonce,
+        network,
+        vault.clone(),
+        signers,
+        funding_coin,
+        outputs.to_vec(),
     )?;
 
-    // Sign vault spend using wallet
-    let spends = ctx.take();
-    let vault_spend = spends.last().unwrap().clone();
-    let vault_sig = hex_string_to_signature(
-        &wallet
-            .sign_coin_spends(vec![vault_spend], false, true)
-            .await?
-            .spend_bundle
-            .aggregated_signature,
-    )?;
+    get_spends_cost(ctx.take(), network)
+}
 
-    // Assemble final bundle and submit
-    let sb = offer.take(SpendBundle::new(spends, security_coin_sig + &vault_sig));
+// Splits the outputs into consecutive batches whose spends cost at most `max_cost`
+pub fn split_issuance_outputs(
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: &MedievalVault,
+    signers: &[PublicKey],
+    outputs: Vec<IssuanceOutput>,
+    max_cost: u64,
+) -> Result<Vec<Vec<IssuanceOutput>>, RcliError> {
+    split_by_cost(
+        outputs,
+        max_cost,
+        |outputs| get_issuance_cost(launcher_id, nonce, network, vault, signers, outputs),
+        |output| format!("Issuing to 0x{}", hex::encode(output.puzzle_hash)),
+    )
+}
 
-    println!("Submitting transaction...");
-    let resp = client.push_tx(sb).await?;
+#[derive(Debug, Deserialize, Clone)]
+pub struct RecipientRecord {
+    pub address: String,
+    pub amount: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
 
-    println!("Transaction submitted; status='{}'", resp.status);
+impl RecipientRecord {
+    pub fn to_issuance_output(&self, network: &Network) -> Result<IssuanceOutput, RcliError> {
+        let address = Address::decode(&self.address)?;
+        if address.prefix != network.prefix() {
+            return Err(RcliError::Custom(format!(
+                "Recipient address {} is not a {} address",
+                self.address,
+                network.prefix()
+            )));
+        }
 
-    wait_for_coin(&client, security_coin.coin_id(), true).await?;
-    println!("Confirmed!");
+        let amount = parse_amount(&self.amount, true)?;
+        if amount == 0 {
+            return Err(RcliError::Custom(format!(
+                "Recipient {} has an amount of 0",
+                self.address
+            )));
+        }
 
-    Ok(())
+        Ok(IssuanceOutput {
+            puzzle_hash: address.puzzle_hash,
+            amount,
+            memo: self
+                .memo
+                .as_ref()
+                .filter(|memo| !memo.is_empty())
+                .map(|memo| Bytes::new(memo.as_bytes().to_vec())),
+        })
+    }
+}
+
+pub fn load_recipients_csv<P: AsRef<Path>>(path: P) -> Result<Vec<RecipientRecord>, RcliError> {
+    let file = File::open(path)?;
+    let mut rdr = ReaderBuilder::new()
+        .has_headers(true)
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(file);
+
+    let mut records = Vec::new();
+    for result in rdr.deserialize() {
+        let record: RecipientRecord = result.map_err(CliError::Csv)?;
+        records.push(record);
+    }
+
+    Ok(records)
 }
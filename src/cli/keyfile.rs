@@ -0,0 +1,328 @@
+use aes_gcm::{
+    Aes256Gcm, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use argon2::Argon2;
+use bip39::Mnemonic;
+use chia::{
+    bls::{
+        PublicKey, SecretKey, Signature, aggregate, master_to_wallet_unhardened,
+        master_to_wallet_unhardened_intermediate, sign,
+    },
+    protocol::{Bytes32, Coin, CoinSpend},
+};
+use chia_puzzle_types::{DeriveSynthetic, Memos};
+use chia_puzzles::SETTLEMENT_PAYMENT_HASH;
+use chia_wallet_sdk::{
+    coinset::ChiaRpcClient,
+    driver::{SpendContext, StandardLayer},
+    prelude::ToTreeHash,
+    signer::{AggSigConstants, RequiredSignature},
+    types::Conditions,
+    utils::Address,
+};
+use clvmr::Allocator;
+use serde::{Deserialize, Serialize};
+
+use std::{env, fs, path::Path};
+
+use crate::{Funding, KEYFILE_ENV_VAR, Network, RcliError, get_rpc_client, progress, record_file};
+
+// If set, the keyfile password is read from this variable instead of a prompt
+pub const KEYFILE_PASSWORD_ENV_VAR: &str = "RCLI_KEYFILE_PASSWORD";
+
+// Coins used to pay for transactions and vault member keys are looked for in
+//  this many derivations
+pub const KEYFILE_DERIVATION_LIMIT: u32 = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeyfileContents {
+    version: u8,
+    kdf: String,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+// Signs using a master secret key read from an encrypted keyfile; fees are
+//  paid with XCH coins found via the configured RPC
+pub struct KeyfileSigner {
+    // Derived once, when the keyfile is loaded
+    keys: Vec<(SecretKey, PublicKey, Bytes32)>,
+    network: Network,
+}
+
+impl KeyfileSigner {
+    pub fn load(path: &str, network: Network) -> Result<Self, RcliError> {
+        let contents = fs::read_to_string(path)?;
+        let keyfile: KeyfileContents =
+            serde_json::from_str(&contents).map_err(|err| RcliError::Custom(err.to_string()))?;
+        if keyfile.version != 1 || keyfile.kdf != "argon2id" {
+            return Err(RcliError::Custom(format!(
+                "Unsupported keyfile version/kdf: {}/{}",
+                keyfile.version, keyfile.kdf
+            )));
+        }
+
+        let password = get_keyfile_password(false)?;
+        let cipher = get_keyfile_cipher(&password, &decode_hex(&keyfile.salt)?)?;
+        let secret = cipher
+            .decrypt(
+                Nonce::from_slice(&decode_hex(&keyfile.nonce)?),
+                decode_hex(&keyfile.ciphertext)?.as_slice(),
+            )
+            .map_err(|_| RcliError::Custom("Wrong keyfile password".to_string()))?;
+        let secret = String::from_utf8(secret).map_err(|err| RcliError::Custom(err.to_string()))?;
+
+        Ok(Self {
+            keys: derive_keys(&parse_secret(&secret)?, KEYFILE_DERIVATION_LIMIT),
+            network,
+        })
+    }
+
+    // Synthetic secret keys, public keys and puzzle hashes of the first
+    //  KEYFILE_DERIVATION_LIMIT unhardened derivations
+    pub fn keys(&self) -> &[(SecretKey, PublicKey, Bytes32)] {
+        &self.keys
+    }
+
+    // Spends the wallet's largest coins to create a settlement payments coin of
+    //  `amount` mojos; the change goes back to the first address
+    pub async fn fund(
+        &self,
+        ctx: &mut SpendContext,
+        amount: u64,
+        fee: u64,
+    ) -> Result<Funding, RcliError> {
+        let keys = &self.keys;
+        let client = get_rpc_client(&self.network)?;
+
+        let resp = client
+            .get_coin_records_by_puzzle_hashes(
+                keys.iter()
+                    .map(|(_, _, puzzle_hash)| *puzzle_hash)
+                    .collect(),
+                None,
+                None,
+                Some(false),
+            )
+            .await?;
+        if !resp.success {
+            return Err(RcliError::Custom(format!(
+                "Could not fetch wallet coins: {}",
+                resp.error.unwrap_or_default()
+            )));
+        }
+        // A wallet without coins has no records - that's an insufficient balance
+        //  below, not an RPC failure
+        let mut coin_records = resp.coin_records.unwrap_or_default();
+        coin_records.sort_unstable_by(|a, b| b.coin.amount.cmp(&a.coin.amount));
+
+        let needed = amount + fee;
+        let mut selected = Vec::new();
+        let mut selected_amount = 0;
+        for coin_record in coin_records {
+            if selected_amount >= needed {
+                break;
+            }
+            selected_amount += coin_record.coin.amount;
+            selected.push(coin_record.coin);
+        }
+        if selected_amount < needed {
+            return Err(RcliError::InsufficientCoins(format!(
+                "need {} mojos, keyfile wallet has {}",
+                needed, selected_amount
+            )));
+        }
+
+        // The first coin creates all outputs; the others only make sure they are
+        //  spent together with it
+        let mut funding_ctx = SpendContext::new();
+        let first_coin_id = selected[0].coin_id();
+        let change_puzzle_hash = keys[0].2;
+        let funding_coin = Coin::new(first_coin_id, SETTLEMENT_PAYMENT_HASH.into(), amount);
+
+        for (i, coin) in selected.iter().enumerate() {
+            let Some((_, public_key, _)) = keys
+                .iter()
+                .find(|(_, _, puzzle_hash)| *puzzle_hash == coin.puzzle_hash)
+            else {
+                return Err(RcliError::Custom(
+                    "Coin does not belong to keyfile".to_string(),
+                ));
+            };
+
+            let conditions = if i == 0 {
+                let mut conditions = Conditions::new()
+                    .create_coin(funding_coin.puzzle_hash, amount, Memos::None)
+                    .reserve_fee(fee);
+                if selected_amount > needed {
+                    conditions = conditions.create_coin(
+                        change_puzzle_hash,
+                        selected_amount - needed,
+                        Memos::None,
+                    );
+                }
+
+                conditions
+            } else {
+                Conditions::new().assert_concurrent_spend(first_coin_id)
+            };
+
+            StandardLayer::new(*public_key).spend(&mut funding_ctx, *coin, conditions)?;
+        }
+
+        let coin_spends = funding_ctx.take();
+        let signature = self.sign_coin_spends(&coin_spends)?;
+        for coin_spend in coin_spends {
+            ctx.insert(coin_spend);
+        }
+
+        Ok(Funding::from_signature(
+            funding_coin,
+            signature,
+            selected.len(),
+            fee,
+        ))
+    }
+
+    pub fn sign_coin_spends(&self, coin_spends: &[CoinSpend]) -> Result<Signature, RcliError> {
+        self.sign_coin_spends_for(coin_spends, None)
+    }
+
+    // Same as `sign_coin_spends`, but only signs for the given public keys (if set)
+    pub fn sign_coin_spends_for(
+        &self,
+        coin_spends: &[CoinSpend],
+        public_keys: Option<&[PublicKey]>,
+    ) -> Result<Signature, RcliError> {
+        let mut allocator = Allocator::new();
+        let required_signatures = RequiredSignature::from_coin_spends(
+            &mut allocator,
+            coin_spends,
+            &AggSigConstants::new(self.network.agg_sig_me_additional_data),
+        )
+        .map_err(|err| RcliError::Custom(format!("Could not get required signatures: {err}")))?;
+
+        let mut signatures = Vec::new();
+        for required in required_signatures {
+            let RequiredSignature::Bls(required) = required else {
+                continue;
+            };
+            if public_keys.is_some_and(|public_keys| !public_keys.contains(&required.public_key)) {
+                continue;
+            }
+            let Some((secret_key, _, _)) = self
+                .keys
+                .iter()
+                .find(|(_, public_key, _)| *public_key == required.public_key)
+            else {
+                continue;
+            };
+
+            signatures.push(sign(secret_key, required.message()));
+        }
+
+        Ok(aggregate(&signatures))
+    }
+}
+
+// Synthetic secret keys, public keys and puzzle hashes of the first `limit`
+//  unhardened derivations
+fn derive_keys(master_sk: &SecretKey, limit: u32) -> Vec<(SecretKey, PublicKey, Bytes32)> {
+    let intermediate_sk = master_to_wallet_unhardened_intermediate(master_sk);
+
+    (0..limit)
+        .map(|index| {
+            let synthetic_sk =
+                master_to_wallet_unhardened(&intermediate_sk, index).derive_synthetic();
+            let public_key = synthetic_sk.public_key();
+            let puzzle_hash = StandardLayer::new(public_key).tree_hash().into();
+
+            (synthetic_sk, public_key, puzzle_hash)
+        })
+        .collect()
+}
+
+// Accepts a mnemonic or a hex-encoded master secret key
+fn parse_secret(secret: &str) -> Result<SecretKey, RcliError> {
+    let secret = secret.trim();
+
+    if let Ok(mnemonic) = Mnemonic::parse_normalized(secret) {
+        return Ok(SecretKey::from_seed(&mnemonic.to_seed("")));
+    }
+
+    let bytes: [u8; 32] = decode_hex(secret)?
+        .try_into()
+        .map_err(|_| RcliError::Custom("Secret is not a mnemonic or 32-byte key".to_string()))?;
+    SecretKey::from_bytes(&bytes).map_err(|err| RcliError::Custom(err.to_string()))
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, RcliError> {
+    hex::decode(value.replace("0x", "")).map_err(|err| RcliError::Custom(err.to_string()))
+}
+
+fn get_keyfile_password(confirm: bool) -> Result<String, RcliError> {
+    if let Ok(password) = env::var(KEYFILE_PASSWORD_ENV_VAR) {
+        return Ok(password);
+    }
+
+    let password = rpassword::prompt_password("Keyfile password: ")?;
+    if confirm && rpassword::prompt_password("Confirm password: ")? != password {
+        return Err(RcliError::Custom("Passwords do not match".to_string()));
+    }
+
+    Ok(password)
+}
+
+fn get_keyfile_cipher(password: &str, salt: &[u8]) -> Result<Aes256Gcm, RcliError> {
+    let mut key = [0u8; 32];
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|err| RcliError::Custom(format!("Could not derive keyfile key: {err}")))?;
+
+    Aes256Gcm::new_from_slice(&key).map_err(|err| RcliError::Custom(err.to_string()))
+}
+
+pub async fn cli_create_keyfile(file: String) -> Result<(), RcliError> {
+    if Path::new(&file).exists() {
+        return Err(RcliError::Custom(format!("File '{}' already exists", file)));
+    }
+
+    let secret = rpassword::prompt_password("Mnemonic or master secret key (hex): ")?;
+    let master_sk = parse_secret(&secret)?;
+    let password = get_keyfile_password(true)?;
+
+    let mut salt = [0u8; 16];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = get_keyfile_cipher(&password, &salt)?
+        .encrypt(&nonce, secret.trim().as_bytes())
+        .map_err(|err| RcliError::Custom(format!("Could not encrypt secret: {err}")))?;
+
+    let contents = serde_json::to_string_pretty(&KeyfileContents {
+        version: 1,
+        kdf: "argon2id".to_string(),
+        salt: hex::encode(salt),
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+    .map_err(|err| RcliError::Custom(err.to_string()))?;
+    fs::write(&file, contents)?;
+
+    let first_puzzle_hash = derive_keys(&master_sk, 1)[0].2;
+    progress!("Keyfile saved to '{}'", file);
+    record_file(&file);
+    progress!(
+        "First address: {} / {}",
+        Address::new(first_puzzle_hash, "xch".to_string()).encode()?,
+        Address::new(first_puzzle_hash, "txch".to_string()).encode()?
+    );
+    progress!(
+        "Set {}={} to use it instead of Sage.",
+        KEYFILE_ENV_VAR,
+        file
+    );
+
+    Ok(())
+}
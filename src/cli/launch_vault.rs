@@ -1,80 +1,210 @@
+use chia::{
+    bls::{PublicKey, Signature},
+    protocol::{Bytes32, Coin},
+};
 use chia_wallet_sdk::{
     coinset::ChiaRpcClient,
     driver::{
-        Launcher, MedievalVaultHint, Offer, SpendContext, create_security_coin, decode_offer,
-        spend_security_coin,
+        Launcher, MedievalVaultHint, SpendContext, create_security_coin, spend_security_coin,
     },
     prelude::{SpendBundle, ToTreeHash},
     types::puzzles::P2MOfNDelegateDirectArgs,
     utils::Address,
 };
-use slot_machine::{
-    CliError, SageClient, assets_xch_only, get_coinset_client, get_constants, get_prefix,
-    no_assets, parse_amount, wait_for_coin,
-};
+use slot_machine::{hex_string_to_pubkey, parse_amount};
 
-use crate::get_first_address;
+use crate::{
+    Network, RcliError, WALLET_DERIVATION_SCAN_LIMIT, WalletSigner, check_profile_can_save,
+    get_first_address, get_rpc_client, get_wallet_public_keys, progress, prompt_profile_name,
+    push_spend_bundle, record_bundle_id, record_file, record_launcher_id, record_status,
+    save_launcher_id_to_profile, simulate_spend_bundle, wait_for_confirmation,
+};
 
-pub async fn cli_launch_vault(fee_str: String, testnet11: bool) -> Result<(), CliError> {
+pub async fn cli_launch_vault(
+    m: usize,
+    members: Option<String>,
+    fee_str: String,
+    dry_run: bool,
+    save_profile: Option<String>,
+    network: &Network,
+) -> Result<(), RcliError> {
     let fee = parse_amount(&fee_str, false)?;
 
     let mut ctx = SpendContext::new();
-    let wallet = SageClient::new()?;
+    let wallet = WalletSigner::new(network)?;
+
+    let pubkeys = if let Some(members) = members {
+        parse_vault_members(&wallet, &members, network).await?
+    } else {
+        let layer = get_first_address(&wallet).await?;
+        progress!(
+            "Using first address: {}",
+            Address::new(layer.tree_hash().into(), network.prefix()).encode()?
+        );
+
+        vec![layer.synthetic_key]
+    };
+
+    if m == 0 || m > pubkeys.len() {
+        return Err(RcliError::Custom(format!(
+            "Invalid threshold: {}-of-{}",
+            m,
+            pubkeys.len()
+        )));
+    }
+    progress!("Vault will be a {}-of-{} multisig:", m, pubkeys.len());
+    for pubkey in &pubkeys {
+        progress!("  {}", hex::encode(pubkey.to_bytes()));
+    }
 
-    let layer = get_first_address(&wallet).await?;
-    println!(
-        "Using first address: {}",
-        Address::new(layer.tree_hash().into(), get_prefix(testnet11)).encode()?
+    // Asked (and checked) before anything is spent
+    let save_profile = match save_profile {
+        Some(name) => Some(name),
+        None if !dry_run => prompt_profile_name()?,
+        None => None,
+    };
+    if let Some(name) = &save_profile {
+        check_profile_can_save(name, network)?;
+    }
+
+    let funding = wallet.fund(&mut ctx, 1, fee, true).await?;
+    progress!("{}", funding);
+
+    let (launcher_id, security_coin, security_coin_sig) =
+        spend_vault_launch(&mut ctx, funding.coin, m, pubkeys, network)?;
+    progress!(
+        "Multisig (medieval launch) launcher id (SAVE THIS): {}",
+        hex::encode(launcher_id.to_bytes())
     );
+    record_launcher_id(launcher_id);
+
+    let offer_id = funding.offer_id();
+    let sb = funding.take(SpendBundle::new(ctx.take(), security_coin_sig));
+    if dry_run {
+        let result = simulate_spend_bundle(&sb, network);
+        wallet.cancel_funding(offer_id).await?;
+        return result;
+    }
+
+    progress!("Submitting transaction...");
+    let client = get_rpc_client(network)?;
+    record_bundle_id(sb.name());
+    let status = push_spend_bundle(&client, sb).await?;
 
-    let offer_resp = wallet
-        .make_offer(no_assets(), assets_xch_only(1), fee, None, None, true)
-        .await?;
-    println!("Offer with id {} created.", offer_resp.offer_id);
+    progress!("Transaction submitted; status='{}'", status);
+    record_status("submitted");
 
-    let offer = Offer::from_spend_bundle(&mut ctx, &decode_offer(&offer_resp.offer)?)?;
-    let (security_sk, security_coin) =
-        create_security_coin(&mut ctx, offer.offered_coins().xch[0])?;
+    wait_for_confirmation(&client, security_coin.coin_id()).await?;
+    progress!("Confirmed!");
+    record_status("confirmed");
+
+    // The vault exists at this point, so failing to save it is only a warning
+    if let Some(name) = save_profile {
+        match save_launcher_id_to_profile(&name, launcher_id, network) {
+            Ok(path) => {
+                progress!(
+                    "Launcher id saved to profile '{}' in '{}'",
+                    name,
+                    path.display()
+                );
+                record_file(&path.display().to_string());
+            }
+            Err(err) => progress!(
+                "Warning: could not save the launcher id to profile '{}': {}",
+                name,
+                err
+            ),
+        }
+    }
+
+    Ok(())
+}
+
+// Spends the funding coin (via a security coin) to launch an m-of-n medieval vault
+// Returns the launcher id, the security coin and its signature
+pub fn spend_vault_launch(
+    ctx: &mut SpendContext,
+    funding_coin: Coin,
+    m: usize,
+    pubkeys: Vec<PublicKey>,
+    network: &Network,
+) -> Result<(Bytes32, Coin, Signature), RcliError> {
+    let (security_sk, security_coin) = create_security_coin(ctx, funding_coin)?;
 
     let launcher = Launcher::new(security_coin.coin_id(), 1);
-    let launcher_coin = launcher.coin();
+    let launcher_id = launcher.coin().coin_id();
 
-    let m = 1;
-    let pubkeys = vec![layer.synthetic_key];
     let launch_hints = MedievalVaultHint {
-        my_launcher_id: launcher_coin.coin_id(),
-        m: 1,
+        my_launcher_id: launcher_id,
+        m,
         public_key_list: pubkeys.clone(),
     };
-    println!(
-        "Multisig (medieval launch) launcher id (SAVE THIS): {}",
-        hex::encode(launcher_coin.coin_id().to_bytes())
-    );
 
     let (create_conditions, _vault_coin) = launcher.spend(
-        &mut ctx,
+        ctx,
         P2MOfNDelegateDirectArgs::curry_tree_hash(m, pubkeys).into(),
         launch_hints,
     )?;
 
     let security_coin_sig = spend_security_coin(
-        &mut ctx,
+        ctx,
         security_coin,
         create_conditions,
         &security_sk,
-        get_constants(testnet11),
+        &network.constants(),
     )?;
 
-    let sb = offer.take(SpendBundle::new(ctx.take(), security_coin_sig));
+    Ok((launcher_id, security_coin, security_coin_sig))
+}
 
-    println!("Submitting transaction...");
-    let client = get_coinset_client(testnet11);
-    let resp = client.push_tx(sb).await?;
+// Parses a comma-separated list of (synthetic) public keys and addresses
+// The public key behind an address can only be found if the address belongs
+//  to the connected wallet
+pub async fn parse_vault_members(
+    wallet: &WalletSigner,
+    members: &str,
+    network: &Network,
+) -> Result<Vec<PublicKey>, RcliError> {
+    let mut wallet_keys = None;
+    let mut pubkeys = Vec::new();
 
-    println!("Transaction submitted; status='{}'", resp.status);
+    for member in members.split(',').map(str::trim) {
+        let pubkey = if let Ok(address) = Address::decode(member) {
+            if address.prefix != network.prefix() {
+                return Err(RcliError::Custom(format!(
+                    "Member {} is not a {} address",
+                    member,
+                    network.prefix()
+                )));
+            }
 
-    wait_for_coin(&client, security_coin.coin_id(), true).await?;
-    println!("Confirmed!");
+            if wallet_keys.is_none() {
+                wallet_keys =
+                    Some(get_wallet_public_keys(wallet, WALLET_DERIVATION_SCAN_LIMIT).await?);
+            }
 
-    Ok(())
+            let Some((pubkey, _)) = wallet_keys
+                .as_ref()
+                .unwrap()
+                .iter()
+                .find(|(_, puzzle_hash)| *puzzle_hash == address.puzzle_hash)
+            else {
+                return Err(RcliError::Custom(format!(
+                    "Address {} does not belong to the connected wallet - please provide its public key instead",
+                    member
+                )));
+            };
+
+            *pubkey
+        } else {
+            hex_string_to_pubkey(&member.replace("0x", ""))?
+        };
+
+        if pubkeys.contains(&pubkey) {
+            return Err(RcliError::Custom(format!("Duplicate member: {}", member)));
+        }
+        pubkeys.push(pubkey);
+    }
+
+    Ok(pubkeys)
 }
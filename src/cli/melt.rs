@@ -0,0 +1,266 @@
+use chia::{
+    bls::PublicKey,
+    protocol::{Bytes, Bytes32, SpendBundle},
+};
+use chia_wallet_sdk::{
+    coinset::ChiaRpcClient,
+    driver::{
+        Cat, MedievalVault, SingletonInfo, SpendContext, create_security_coin, spend_security_coin,
+    },
+    types::{Conditions, Mod, puzzles::RevocationArgs},
+};
+use slot_machine::{CliError, hex_string_to_bytes32, parse_amount};
+
+use crate::{
+    EverythingWithSingletonTailArgs, EverythingWithSingletonTailSolution, Network,
+    PendingVaultSpend, RcliError, VaultSpendOptions, WalletSigner, complete_vault_spend,
+    get_rcat_asset_id, get_rcat_hidden_puzzle_hash, get_rpc_client, parse_rcat_coins, progress,
+    record_coin_ids, record_rcat, select_vault_signers, spend_rcats_via_vault, sync_vault,
+};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn cli_melt(
+    launcher_id_str: String,
+    nonce: u64,
+    coin_ids_str: String,
+    amount_str: Option<String>,
+    fee_str: String,
+    export: Option<String>,
+    signers: Option<String>,
+    dry_run: bool,
+    network: &Network,
+) -> Result<(), RcliError> {
+    let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+    let fee = parse_amount(&fee_str, false)?;
+    let spend_options = VaultSpendOptions::parse(export, signers, dry_run)?;
+    let coin_ids = coin_ids_str
+        .replace("0x", "")
+        .split(',')
+        .map(hex_string_to_bytes32)
+        .collect::<Result<Vec<Bytes32>, CliError>>()?;
+
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    record_rcat(launcher_id, nonce);
+    progress!(
+        "rCAT asset id (nonce {}): {:}",
+        nonce,
+        hex::encode(asset_id)
+    );
+
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+    progress!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
+
+    progress!("Fetching rCAT coin records...");
+    let coin_ids_len = coin_ids.len();
+    let client = get_rpc_client(network)?;
+    let Some(coin_records) = client
+        .get_coin_records_by_names(coin_ids, None, None, Some(true))
+        .await?
+        .coin_records
+    else {
+        return Err(RcliError::Custom("Error fetching coin records".to_string()));
+    };
+    if coin_records.len() != coin_ids_len {
+        return Err(RcliError::Custom(
+            "Could not find one or more rCAT coins on-chain".to_string(),
+        ));
+    }
+
+    let mut ctx = SpendContext::new();
+
+    let vault = sync_vault(&client, &mut ctx, launcher_id).await?;
+
+    progress!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+
+    let cats = parse_rcat_coins(
+        &client,
+        &mut ctx,
+        coin_records,
+        asset_id,
+        hidden_puzzle_hash,
+    )
+    .await?;
+    if cats.is_empty() {
+        return Err(RcliError::Custom("No coins to melt".to_string()));
+    }
+
+    record_coin_ids(cats.iter().map(|cat| cat.coin.coin_id()));
+
+    let total_cat_amount = cats.iter().map(|cat| cat.coin.amount).sum::<u64>();
+    let melt_amount = if let Some(amount_str) = amount_str {
+        parse_amount(&amount_str, true)?
+    } else {
+        total_cat_amount
+    };
+    if melt_amount == 0 || melt_amount > total_cat_amount {
+        return Err(RcliError::Custom(format!(
+            "Invalid melt amount: {:.3} (selected coins hold {:.3})",
+            melt_amount as f64 / 1000.0,
+            total_cat_amount as f64 / 1000.0
+        )));
+    }
+
+    progress!(
+        "Melting {:.3} rCATs from {} coins (total amount {:.3})...",
+        melt_amount as f64 / 1000.0,
+        cats.len(),
+        total_cat_amount as f64 / 1000.0
+    );
+
+    let wallet = WalletSigner::new(network)?;
+    let signers = select_vault_signers(&wallet, &vault, &spend_options).await?;
+    let vault_coin_id = vault.coin.coin_id();
+    let funding = wallet.fund(&mut ctx, 1, fee, false).await?;
+    progress!("{}", funding);
+
+    // Create security coin
+    let (security_sk, security_coin) = create_security_coin(&mut ctx, funding.coin)?;
+
+    let security_coin_sig = spend_security_coin(
+        &mut ctx,
+        security_coin,
+        Conditions::new().assert_concurrent_spend(cats[0].coin.coin_id()),
+        &security_sk,
+        &network.constants(),
+    )?;
+
+    spend_melt(
+        &mut ctx,
+        launcher_id,
+        nonce,
+        network,
+        vault,
+        &signers,
+        cats,
+        melt_amount,
+    )?;
+
+    let offer_id = funding.offer_id();
+    // Assemble bundle - the vault spend still needs to be signed by its members
+    let sb = funding.take(SpendBundle::new(ctx.take(), security_coin_sig));
+
+    let result = complete_vault_spend(
+        &client,
+        &wallet,
+        PendingVaultSpend {
+            launcher_id,
+            network: network.clone(),
+            signers,
+            vault_coin_id,
+            confirmation_coin_id: security_coin.coin_id(),
+            spend_bundle: sb,
+            signatures: vec![],
+        },
+        &spend_options,
+    )
+    .await;
+    if spend_options.dry_run {
+        wallet.cancel_funding(offer_id).await?;
+    }
+
+    result
+}
+
+// Spends the rCATs and the vault, melting `melt_amount` - coins are melted in
+//  order; whatever is not melted goes back to the coin's owner
+#[allow(clippy::too_many_arguments)]
+pub fn spend_melt(
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: MedievalVault,
+    signers: &[PublicKey],
+    cats: Vec<Cat>,
+    melt_amount: u64,
+) -> Result<(), RcliError> {
+    if cats.is_empty() {
+        return Err(RcliError::Custom("No coins to melt".to_string()));
+    }
+
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+    let singleton_inner_puzzle_hash: Bytes32 = vault.info.inner_puzzle_hash().into();
+    let first_cat_coin_id = cats[0].coin.coin_id();
+
+    let mut left_to_melt = melt_amount;
+    let mut cats_and_conditions = Vec::with_capacity(cats.len());
+    for (i, cat) in cats.into_iter().enumerate() {
+        let to_melt = left_to_melt.min(cat.coin.amount);
+        left_to_melt -= to_melt;
+
+        let mut conditions = Conditions::new();
+        if to_melt < cat.coin.amount {
+            let owner_ph = RevocationArgs::new(hidden_puzzle_hash, cat.p2_puzzle_hash())
+                .curry_tree_hash()
+                .into();
+            let owner_hint = ctx.hint(cat.p2_puzzle_hash())?;
+
+            conditions = conditions.create_coin(owner_ph, cat.coin.amount - to_melt, owner_hint);
+        }
+
+        // The first rCAT runs the TAIL for the whole ring
+        if i == 0 {
+            conditions = add_rcat_tail_conditions(
+                ctx,
+                launcher_id,
+                nonce,
+                singleton_inner_puzzle_hash,
+                conditions,
+            )?;
+        }
+
+        cats_and_conditions.push((cat, conditions));
+    }
+
+    let mut vault_conditions = spend_rcats_via_vault(
+        ctx,
+        launcher_id,
+        singleton_inner_puzzle_hash,
+        cats_and_conditions,
+    )?;
+    vault_conditions =
+        add_melt_approval_conditions(ctx, first_cat_coin_id, melt_amount, vault_conditions)?;
+
+    // Spend vault
+    let vault_hint = ctx.hint(launcher_id)?;
+    vault_conditions = vault_conditions.create_coin(
+        vault.info.inner_puzzle_hash().into(),
+        vault.coin.amount,
+        vault_hint,
+    );
+    vault.spend(ctx, signers, vault_conditions, network.genesis_challenge)?;
+
+    Ok(())
+}
+
+// Makes the rCAT run the vault's TAIL - Cat::spend_all will set the extra delta
+//  based on the amount the ring creates
+pub fn add_rcat_tail_conditions(
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    nonce: u64,
+    singleton_inner_puzzle_hash: Bytes32,
+    conditions: Conditions,
+) -> Result<Conditions, RcliError> {
+    let tail = ctx.curry(EverythingWithSingletonTailArgs::new(launcher_id, nonce))?;
+    let tail_solution = ctx.alloc(&EverythingWithSingletonTailSolution {
+        singleton_inner_puzzle_hash,
+    })?;
+
+    Ok(conditions.run_cat_tail(tail, tail_solution))
+}
+
+// The TAIL only runs if the vault sends the CAT's extra delta (negative
+//  when melting) to the coin running it
+pub fn add_melt_approval_conditions(
+    ctx: &mut SpendContext,
+    tail_cat_coin_id: Bytes32,
+    melt_amount: u64,
+    vault_conditions: Conditions,
+) -> Result<Conditions, RcliError> {
+    let delta = ctx.alloc(&-(melt_amount as i64))?;
+    let delta = ctx.extract::<Bytes>(delta)?;
+    let receiver_coin_id = ctx.alloc(&tail_cat_coin_id)?;
+
+    Ok(vault_conditions.send_message(23, delta, vec![receiver_coin_id]))
+}
@@ -0,0 +1,463 @@
+use chia::{
+    bls::{PublicKey, Signature, aggregate, aggregate_verify},
+    protocol::{Bytes32, CoinSpend, SpendBundle},
+    traits::Streamable,
+};
+use chia_wallet_sdk::{
+    coinset::ChiaRpcClient,
+    driver::MedievalVault,
+    signer::{AggSigConstants, RequiredSignature},
+};
+use clvmr::Allocator;
+use serde::{Deserialize, Serialize};
+use slot_machine::{
+    CliError, hex_string_to_bytes32, hex_string_to_pubkey, hex_string_to_signature,
+};
+use std::fs;
+
+use crate::{
+    Network, NetworkFile, RcliError, RpcClient, WALLET_DERIVATION_SCAN_LIMIT, WalletSigner,
+    get_rpc_client, get_vault_signing_keys, get_wallet_public_keys, progress, push_spend_bundle,
+    record_bundle_id, record_file, record_launcher_id, record_status, simulate_spend_bundle,
+    wait_for_confirmation,
+};
+
+// How vault spends are signed: by the connected wallet (default), or exported
+//  to a file so each vault member can sign it with their own wallet
+// Dry runs are signed by the wallet, validated locally and never submitted
+#[derive(Debug, Clone, Default)]
+pub struct VaultSpendOptions {
+    pub export: Option<String>,
+    pub signers: Option<Vec<PublicKey>>,
+    pub dry_run: bool,
+}
+
+impl VaultSpendOptions {
+    pub fn parse(
+        export: Option<String>,
+        signers: Option<String>,
+        dry_run: bool,
+    ) -> Result<Self, RcliError> {
+        if dry_run && export.is_some() {
+            return Err(RcliError::Custom(
+                "--dry-run cannot be used together with --export".to_string(),
+            ));
+        }
+
+        let signers = signers
+            .map(|signers| {
+                signers
+                    .split(',')
+                    .map(|signer| hex_string_to_pubkey(&signer.trim().replace("0x", "")))
+                    .collect::<Result<Vec<PublicKey>, CliError>>()
+            })
+            .transpose()?;
+
+        Ok(Self {
+            export,
+            signers,
+            dry_run,
+        })
+    }
+}
+
+// Picks the m vault members that will sign the vault spend (in vault order)
+// When exporting, defaults to the first m members
+pub async fn select_vault_signers(
+    wallet: &WalletSigner,
+    vault: &MedievalVault,
+    options: &VaultSpendOptions,
+) -> Result<Vec<PublicKey>, RcliError> {
+    let members = &vault.info.public_key_list;
+
+    if let Some(signers) = &options.signers {
+        for signer in signers {
+            if !members.contains(signer) {
+                return Err(RcliError::Custom(format!(
+                    "Signer {} is not a vault member",
+                    hex::encode(signer.to_bytes())
+                )));
+            }
+        }
+
+        let signers = members
+            .iter()
+            .filter(|member| signers.contains(member))
+            .copied()
+            .collect::<Vec<PublicKey>>();
+        if signers.len() != vault.info.m {
+            return Err(RcliError::Custom(format!(
+                "Expected {} signers, got {}",
+                vault.info.m,
+                signers.len()
+            )));
+        }
+
+        return Ok(signers);
+    }
+
+    if options.export.is_some() {
+        return Ok(members.iter().take(vault.info.m).copied().collect());
+    }
+
+    get_vault_signing_keys(wallet, vault).await
+}
+
+// A bundle that only lacks the vault members' signatures
+#[derive(Debug, Clone)]
+pub struct PendingVaultSpend {
+    pub launcher_id: Bytes32,
+    pub network: Network,
+    pub signers: Vec<PublicKey>,
+    pub vault_coin_id: Bytes32,
+    // Coin that gets spent when the bundle is confirmed
+    pub confirmation_coin_id: Bytes32,
+    pub spend_bundle: SpendBundle,
+    pub signatures: Vec<(Vec<PublicKey>, Signature)>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PendingVaultSpendFile {
+    launcher_id: String,
+    network: NetworkFile,
+    signers: Vec<String>,
+    vault_coin_id: String,
+    confirmation_coin_id: String,
+    spend_bundle: String,
+    signatures: Vec<MemberSignatureFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MemberSignatureFile {
+    public_keys: Vec<String>,
+    signature: String,
+}
+
+impl PendingVaultSpend {
+    pub fn vault_spend(&self) -> Result<CoinSpend, RcliError> {
+        self.spend_bundle
+            .coin_spends
+            .iter()
+            .find(|cs| cs.coin.coin_id() == self.vault_coin_id)
+            .cloned()
+            .ok_or_else(|| RcliError::Custom("Vault spend not found in bundle".to_string()))
+    }
+
+    pub fn signed_by(&self, public_key: &PublicKey) -> bool {
+        self.signatures
+            .iter()
+            .any(|(keys, _)| keys.contains(public_key))
+    }
+
+    pub fn missing_signers(&self) -> Vec<PublicKey> {
+        self.signers
+            .iter()
+            .filter(|signer| !self.signed_by(signer))
+            .copied()
+            .collect()
+    }
+
+    // Checks that the signature covers all AGG_SIG conditions the vault spend
+    //  requires from the given public keys
+    pub fn verify_signature(
+        &self,
+        public_keys: &[PublicKey],
+        signature: &Signature,
+    ) -> Result<bool, RcliError> {
+        let mut allocator = Allocator::new();
+        let required_signatures = RequiredSignature::from_coin_spends(
+            &mut allocator,
+            &[self.vault_spend()?],
+            &AggSigConstants::new(self.network.agg_sig_me_additional_data),
+        )
+        .map_err(|err| RcliError::Custom(format!("Could not get required signatures: {err}")))?;
+
+        let messages = required_signatures
+            .into_iter()
+            .filter_map(|required| match required {
+                RequiredSignature::Bls(required) if public_keys.contains(&required.public_key) => {
+                    Some((required.public_key, required.message()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        Ok(aggregate_verify(
+            signature,
+            messages
+                .iter()
+                .map(|(pk, message)| (pk, message.as_slice())),
+        ))
+    }
+
+    pub fn add_signature(
+        &mut self,
+        public_keys: Vec<PublicKey>,
+        signature: Signature,
+    ) -> Result<(), RcliError> {
+        if !self.verify_signature(&public_keys, &signature)? {
+            return Err(RcliError::Custom(
+                "Signature is not valid for the vault spend".to_string(),
+            ));
+        }
+
+        // Each key must be counted once in the aggregated signature, so earlier
+        //  signatures covered by this one are replaced
+        if self.signatures.iter().any(|(keys, _)| {
+            keys.iter().any(|key| public_keys.contains(key))
+                && !keys.iter().all(|key| public_keys.contains(key))
+        }) {
+            return Err(RcliError::Custom(
+                "Signature partially overlaps an existing signature".to_string(),
+            ));
+        }
+        self.signatures
+            .retain(|(keys, _)| !keys.iter().all(|key| public_keys.contains(key)));
+
+        self.signatures.push((public_keys, signature));
+        Ok(())
+    }
+
+    // Aggregates member signatures with the bundle's signature
+    pub fn signed_spend_bundle(&self) -> Result<SpendBundle, RcliError> {
+        let missing_signers = self.missing_signers();
+        if !missing_signers.is_empty() {
+            return Err(RcliError::Custom(format!(
+                "Missing signatures from {} of {} signers: {}",
+                missing_signers.len(),
+                self.signers.len(),
+                missing_signers
+                    .iter()
+                    .map(|pk| hex::encode(pk.to_bytes()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )));
+        }
+
+        let aggregated_signature = aggregate(
+            std::iter::once(&self.spend_bundle.aggregated_signature)
+                .chain(self.signatures.iter().map(|(_, signature)| signature)),
+        );
+
+        Ok(SpendBundle::new(
+            self.spend_bundle.coin_spends.clone(),
+            aggregated_signature,
+        ))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), RcliError> {
+        let file = PendingVaultSpendFile {
+            launcher_id: hex::encode(self.launcher_id),
+            network: self.network.to_file(),
+            signers: self
+                .signers
+                .iter()
+                .map(|pk| hex::encode(pk.to_bytes()))
+                .collect(),
+            vault_coin_id: hex::encode(self.vault_coin_id),
+            confirmation_coin_id: hex::encode(self.confirmation_coin_id),
+            spend_bundle: hex::encode(
+                self.spend_bundle
+                    .to_bytes()
+                    .map_err(|err| RcliError::Custom(err.to_string()))?,
+            ),
+            signatures: self
+                .signatures
+                .iter()
+                .map(|(public_keys, signature)| MemberSignatureFile {
+                    public_keys: public_keys
+                        .iter()
+                        .map(|pk| hex::encode(pk.to_bytes()))
+                        .collect(),
+                    signature: hex::encode(signature.to_bytes()),
+                })
+                .collect(),
+        };
+
+        let contents = serde_json::to_string_pretty(&file)
+            .map_err(|err| RcliError::Custom(err.to_string()))?;
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, RcliError> {
+        let contents = fs::read_to_string(path)?;
+        let file: PendingVaultSpendFile =
+            serde_json::from_str(&contents).map_err(|err| RcliError::Custom(err.to_string()))?;
+
+        let spend_bundle_bytes =
+            hex::decode(&file.spend_bundle).map_err(|err| RcliError::Custom(err.to_string()))?;
+
+        let mut signatures = Vec::with_capacity(file.signatures.len());
+        for member_signature in file.signatures {
+            signatures.push((
+                member_signature
+                    .public_keys
+                    .iter()
+                    .map(|pk| hex_string_to_pubkey(pk))
+                    .collect::<Result<Vec<PublicKey>, CliError>>()?,
+                hex_string_to_signature(&member_signature.signature)?,
+            ));
+        }
+
+        Ok(Self {
+            launcher_id: hex_string_to_bytes32(&file.launcher_id)?,
+            network: Network::from_file(file.network)?,
+            signers: file
+                .signers
+                .iter()
+                .map(|pk| hex_string_to_pubkey(pk))
+                .collect::<Result<Vec<PublicKey>, CliError>>()?,
+            vault_coin_id: hex_string_to_bytes32(&file.vault_coin_id)?,
+            confirmation_coin_id: hex_string_to_bytes32(&file.confirmation_coin_id)?,
+            spend_bundle: SpendBundle::from_bytes(&spend_bundle_bytes)
+                .map_err(|err| RcliError::Custom(err.to_string()))?,
+            signatures,
+        })
+    }
+}
+
+// Uses the connected wallet to sign for the missing signers it controls
+pub async fn sign_pending_vault_spend(
+    wallet: &WalletSigner,
+    pending: &mut PendingVaultSpend,
+) -> Result<(), RcliError> {
+    let wallet_keys = get_wallet_public_keys(wallet, WALLET_DERIVATION_SCAN_LIMIT).await?;
+    let controlled_signers = pending
+        .signers
+        .iter()
+        .filter(|signer| wallet_keys.iter().any(|(key, _)| key == *signer))
+        .copied()
+        .collect::<Vec<PublicKey>>();
+    let public_keys = controlled_signers
+        .iter()
+        .filter(|signer| !pending.signed_by(signer))
+        .copied()
+        .collect::<Vec<PublicKey>>();
+    if public_keys.is_empty() {
+        return Err(RcliError::Custom(
+            "Connected wallet does not control any of the missing signers".to_string(),
+        ));
+    }
+
+    let vault_spend = pending.vault_spend()?;
+    match wallet {
+        WalletSigner::Keyfile(signer) => {
+            let signature = signer.sign_coin_spends_for(&[vault_spend], Some(&public_keys))?;
+            pending.add_signature(public_keys, signature)
+        }
+        // Sage signs for every key it controls, including signers that already signed
+        WalletSigner::Sage(_) => {
+            let signature = wallet.sign_coin_spends(vec![vault_spend]).await?;
+            pending.add_signature(controlled_signers, signature)
+        }
+    }
+}
+
+pub async fn submit_pending_vault_spend(
+    client: &RpcClient,
+    pending: &PendingVaultSpend,
+) -> Result<(), RcliError> {
+    let sb = pending.signed_spend_bundle()?;
+
+    progress!("Submitting transaction...");
+    record_bundle_id(sb.name());
+    let status = push_spend_bundle(client, sb).await?;
+
+    progress!("Transaction submitted; status='{}'", status);
+    record_status("submitted");
+
+    wait_for_confirmation(client, pending.confirmation_coin_id).await?;
+    progress!("Confirmed!");
+    record_status("confirmed");
+
+    Ok(())
+}
+
+// Signs the vault spend using the wallet and submits the bundle or, if
+//  exporting, saves it so vault members can sign it separately
+// Dry runs stop after validating the signed bundle locally
+pub async fn complete_vault_spend(
+    client: &RpcClient,
+    wallet: &WalletSigner,
+    pending: PendingVaultSpend,
+    options: &VaultSpendOptions,
+) -> Result<(), RcliError> {
+    complete_vault_spend_with(client, wallet, pending, options, |_| Ok(())).await
+}
+
+// Same as `complete_vault_spend`, but calls `on_submit` with the signed bundle
+//  right before it is pushed
+pub async fn complete_vault_spend_with<F>(
+    client: &RpcClient,
+    wallet: &WalletSigner,
+    mut pending: PendingVaultSpend,
+    options: &VaultSpendOptions,
+    on_submit: F,
+) -> Result<(), RcliError>
+where
+    F: FnOnce(&SpendBundle) -> Result<(), RcliError>,
+{
+    if let Some(export) = &options.export {
+        pending.save(export)?;
+        progress!("Unsigned vault spend saved to '{}'", export);
+        record_status("exported");
+        record_file(export);
+        progress!(
+            "Each signer should now run 'rcli sign-vault-spend --file {}'; afterwards, run 'rcli submit-vault-spend --file {}'",
+            export,
+            export
+        );
+
+        return Ok(());
+    }
+
+    sign_pending_vault_spend(wallet, &mut pending).await?;
+    if options.dry_run {
+        return simulate_spend_bundle(&pending.signed_spend_bundle()?, &pending.network);
+    }
+
+    on_submit(&pending.signed_spend_bundle()?)?;
+    submit_pending_vault_spend(client, &pending).await
+}
+
+pub async fn cli_sign_vault_spend(file: String) -> Result<(), RcliError> {
+    let mut pending = PendingVaultSpend::load(&file)?;
+    record_launcher_id(pending.launcher_id);
+    record_file(&file);
+    progress!(
+        "Signing spend of vault {} (coin 0x{})...",
+        hex::encode(pending.launcher_id),
+        hex::encode(pending.vault_coin_id)
+    );
+
+    let wallet = WalletSigner::new(&pending.network)?;
+    sign_pending_vault_spend(&wallet, &mut pending).await?;
+    pending.save(&file)?;
+
+    let missing_signers = pending.missing_signers();
+    progress!(
+        "Signature added; {} of {} signers still need to sign.",
+        missing_signers.len(),
+        pending.signers.len()
+    );
+
+    Ok(())
+}
+
+pub async fn cli_submit_vault_spend(file: String) -> Result<(), RcliError> {
+    let pending = PendingVaultSpend::load(&file)?;
+    record_launcher_id(pending.launcher_id);
+
+    for (public_keys, signature) in &pending.signatures {
+        if !pending.verify_signature(public_keys, signature)? {
+            return Err(RcliError::Custom(
+                "File contains an invalid signature".to_string(),
+            ));
+        }
+    }
+
+    let client = get_rpc_client(&pending.network)?;
+    submit_pending_vault_spend(&client, &pending).await
+}
@@ -0,0 +1,302 @@
+use chia::{consensus::consensus_constants::ConsensusConstants, protocol::Bytes32, sha2::Sha256};
+use chia_wallet_sdk::types::{MAINNET_CONSTANTS, TESTNET11_CONSTANTS};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use slot_machine::hex_string_to_bytes32;
+use std::fmt;
+
+use crate::RcliError;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkKind {
+    #[default]
+    Mainnet,
+    Testnet11,
+    // Any other network (a simulator, a private testnet, ...); has its own
+    //  genesis challenge, address prefix and RPC endpoint
+    Custom,
+}
+
+// Consensus constants a custom network changes from its base network's
+//  (only set in config profiles)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConstantsOverrides {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_block_cost_clvm: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_per_byte: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hard_fork_height: Option<u32>,
+}
+
+impl ConstantsOverrides {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// The network a command runs on - decides the consensus constants (signatures,
+//  validation), the address prefix and which RPC is used
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub kind: NetworkKind,
+    pub genesis_challenge: Bytes32,
+    pub agg_sig_me_additional_data: Bytes32,
+    pub address_prefix: String,
+    // Full node RPC; if not set, mainnet and testnet11 use coinset.org
+    pub rpc_url: Option<String>,
+    // Network whose consensus constants (cost limits, fork heights) are used;
+    //  mainnet or testnet11 - for those two, it's the network itself
+    pub constants_base: NetworkKind,
+    pub constants_overrides: ConstantsOverrides,
+}
+
+impl Network {
+    pub fn mainnet() -> Self {
+        Self {
+            kind: NetworkKind::Mainnet,
+            genesis_challenge: MAINNET_CONSTANTS.genesis_challenge,
+            agg_sig_me_additional_data: MAINNET_CONSTANTS.agg_sig_me_additional_data,
+            address_prefix: "xch".to_string(),
+            rpc_url: None,
+            constants_base: NetworkKind::Mainnet,
+            constants_overrides: ConstantsOverrides::default(),
+        }
+    }
+
+    pub fn testnet11() -> Self {
+        Self {
+            kind: NetworkKind::Testnet11,
+            genesis_challenge: TESTNET11_CONSTANTS.genesis_challenge,
+            agg_sig_me_additional_data: TESTNET11_CONSTANTS.agg_sig_me_additional_data,
+            address_prefix: "txch".to_string(),
+            rpc_url: None,
+            constants_base: NetworkKind::Testnet11,
+            constants_overrides: ConstantsOverrides::default(),
+        }
+    }
+
+    // AGG_SIG_ME additional data defaults to the genesis challenge, like on mainnet
+    // Consensus constants are the base network's (testnet11 by default), with overrides
+    pub fn custom(
+        genesis_challenge: Bytes32,
+        agg_sig_me_additional_data: Option<Bytes32>,
+        address_prefix: String,
+        rpc_url: Option<String>,
+        constants_base: NetworkKind,
+        constants_overrides: ConstantsOverrides,
+    ) -> Self {
+        Self {
+            kind: NetworkKind::Custom,
+            genesis_challenge,
+            agg_sig_me_additional_data: agg_sig_me_additional_data.unwrap_or(genesis_challenge),
+            address_prefix,
+            rpc_url,
+            constants_base,
+            constants_overrides,
+        }
+    }
+
+    // Builds the network from the command-line options (and profile)
+    #[allow(clippy::too_many_arguments)]
+    pub fn parse(
+        kind: NetworkKind,
+        genesis_challenge: Option<String>,
+        agg_sig_me_additional_data: Option<String>,
+        address_prefix: Option<String>,
+        rpc_url: Option<String>,
+        constants_base: Option<NetworkKind>,
+        constants_overrides: ConstantsOverrides,
+    ) -> Result<Self, RcliError> {
+        let mut network = match kind {
+            NetworkKind::Mainnet | NetworkKind::Testnet11 => {
+                if genesis_challenge.is_some()
+                    || agg_sig_me_additional_data.is_some()
+                    || address_prefix.is_some()
+                    || constants_base.is_some()
+                    || !constants_overrides.is_empty()
+                {
+                    return Err(RcliError::Custom(
+                        "--genesis-challenge, --agg-sig-me-data, --address-prefix, --constants-base and constant overrides can only be used with --network custom"
+                            .to_string(),
+                    ));
+                }
+
+                if kind == NetworkKind::Mainnet {
+                    Self::mainnet()
+                } else {
+                    Self::testnet11()
+                }
+            }
+            NetworkKind::Custom => {
+                let (Some(genesis_challenge), Some(address_prefix)) =
+                    (genesis_challenge, address_prefix)
+                else {
+                    return Err(RcliError::Custom(
+                        "Custom networks need --genesis-challenge and --address-prefix".to_string(),
+                    ));
+                };
+
+                let constants_base = constants_base.unwrap_or(NetworkKind::Testnet11);
+                check_constants_base(constants_base)?;
+
+                Self::custom(
+                    hex_string_to_bytes32(&genesis_challenge)?,
+                    agg_sig_me_additional_data
+                        .map(|data| hex_string_to_bytes32(&data))
+                        .transpose()?,
+                    address_prefix,
+                    None,
+                    constants_base,
+                    constants_overrides,
+                )
+            }
+        };
+        network.rpc_url = rpc_url;
+
+        Ok(network)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            NetworkKind::Mainnet => "mainnet",
+            NetworkKind::Testnet11 => "testnet11",
+            NetworkKind::Custom => "custom",
+        }
+    }
+
+    pub fn prefix(&self) -> String {
+        self.address_prefix.clone()
+    }
+
+    // Custom networks use their base network's constants (cost limits, fork heights)
+    //  with their own genesis challenge, signature domains and overrides
+    pub fn constants(&self) -> ConsensusConstants {
+        match self.kind {
+            NetworkKind::Mainnet => MAINNET_CONSTANTS.clone(),
+            NetworkKind::Testnet11 => TESTNET11_CONSTANTS.clone(),
+            NetworkKind::Custom => {
+                let data = self.agg_sig_me_additional_data;
+                let mut constants = match self.constants_base {
+                    NetworkKind::Mainnet => MAINNET_CONSTANTS.clone(),
+                    _ => TESTNET11_CONSTANTS.clone(),
+                };
+                constants.genesis_challenge = self.genesis_challenge;
+                constants.agg_sig_me_additional_data = data;
+                constants.agg_sig_parent_additional_data = agg_sig_additional_data(data, 43);
+                constants.agg_sig_puzzle_additional_data = agg_sig_additional_data(data, 44);
+                constants.agg_sig_amount_additional_data = agg_sig_additional_data(data, 45);
+                constants.agg_sig_puzzle_amount_additional_data = agg_sig_additional_data(data, 46);
+                constants.agg_sig_parent_amount_additional_data = agg_sig_additional_data(data, 47);
+                constants.agg_sig_parent_puzzle_additional_data = agg_sig_additional_data(data, 48);
+
+                let overrides = self.constants_overrides;
+                if let Some(max_block_cost_clvm) = overrides.max_block_cost_clvm {
+                    constants.max_block_cost_clvm = max_block_cost_clvm;
+                }
+                if let Some(cost_per_byte) = overrides.cost_per_byte {
+                    constants.cost_per_byte = cost_per_byte;
+                }
+                if let Some(hard_fork_height) = overrides.hard_fork_height {
+                    constants.hard_fork_height = hard_fork_height;
+                }
+                constants
+            }
+        }
+    }
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            NetworkKind::Custom => write!(
+                f,
+                "custom (genesis challenge 0x{}, prefix '{}', {} constants)",
+                hex::encode(self.genesis_challenge),
+                self.address_prefix,
+                if self.constants_base == NetworkKind::Mainnet {
+                    "mainnet"
+                } else {
+                    "testnet11"
+                }
+            ),
+            _ => write!(f, "{}", self.name()),
+        }
+    }
+}
+
+// Custom networks use mainnet's or testnet11's constants as a base
+fn check_constants_base(constants_base: NetworkKind) -> Result<(), RcliError> {
+    if constants_base == NetworkKind::Custom {
+        return Err(RcliError::Custom(
+            "Constants base must be mainnet or testnet11".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+// The additional data of the other AGG_SIG_* conditions is derived from
+//  AGG_SIG_ME's by appending the condition's opcode and hashing
+fn agg_sig_additional_data(agg_sig_me_additional_data: Bytes32, opcode: u8) -> Bytes32 {
+    let mut hasher = Sha256::new();
+    hasher.update(agg_sig_me_additional_data);
+    hasher.update([opcode]);
+    hasher.finalize().into()
+}
+
+// How a network is stored in files (e.g., exported vault spends)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkFile {
+    pub kind: NetworkKind,
+    pub genesis_challenge: String,
+    pub agg_sig_me_additional_data: String,
+    pub address_prefix: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rpc_url: Option<String>,
+    pub constants_base: NetworkKind,
+    #[serde(default, skip_serializing_if = "ConstantsOverrides::is_empty")]
+    pub constants_overrides: ConstantsOverrides,
+}
+
+impl Network {
+    pub fn to_file(&self) -> NetworkFile {
+        NetworkFile {
+            kind: self.kind,
+            genesis_challenge: hex::encode(self.genesis_challenge),
+            agg_sig_me_additional_data: hex::encode(self.agg_sig_me_additional_data),
+            address_prefix: self.address_prefix.clone(),
+            rpc_url: self.rpc_url.clone(),
+            constants_base: self.constants_base,
+            constants_overrides: self.constants_overrides,
+        }
+    }
+
+    pub fn from_file(file: NetworkFile) -> Result<Self, RcliError> {
+        if file.kind == NetworkKind::Custom {
+            check_constants_base(file.constants_base)?;
+        } else if file.constants_base != file.kind || !file.constants_overrides.is_empty() {
+            return Err(RcliError::Custom(
+                "Only custom networks can change their consensus constants".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            kind: file.kind,
+            genesis_challenge: hex_string_to_bytes32(&file.genesis_challenge)?,
+            agg_sig_me_additional_data: hex_string_to_bytes32(&file.agg_sig_me_additional_data)?,
+            address_prefix: file.address_prefix,
+            rpc_url: file.rpc_url,
+            constants_base: file.constants_base,
+            constants_overrides: file.constants_overrides,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use chia::protocol::{Bytes32, CoinSpend};
+use chia_wallet_sdk::{
+    coinset::{ChiaRpcClient, CoinRecord},
+    driver::{CatLayer, Layer, Puzzle, RevocationLayer, SpendContext},
+    types::{Condition, Mod, puzzles::RevocationSolution},
+};
+use clvmr::NodePtr;
+use slot_machine::hex_string_to_bytes32;
+
+use crate::{
+    EVERYTHING_WITH_SINGLETON_TAIL_HASH, EverythingWithSingletonTailArgs, Network, RcliError,
+    RpcClient, get_rcat_asset_id, get_rpc_client, get_sent_messages, get_vault_spends, progress,
+    record_launcher_id,
+};
+
+pub async fn cli_nonces(launcher_id_str: String, network: &Network) -> Result<(), RcliError> {
+    let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+    record_launcher_id(launcher_id);
+
+    let mut ctx = SpendContext::new();
+    let client = get_rpc_client(network)?;
+
+    progress!("Looking for past issuances...");
+    let nonces = get_vault_nonces(&client, &mut ctx, launcher_id).await?;
+
+    if nonces.is_empty() {
+        progress!("This vault has not issued any rCATs yet.");
+        return Ok(());
+    }
+
+    for nonce in nonces {
+        progress!(
+            "Nonce {}: rCAT asset id {}",
+            nonce,
+            hex::encode(get_rcat_asset_id(launcher_id, nonce))
+        );
+    }
+
+    Ok(())
+}
+
+// Returns the sorted list of nonces for which the vault approved an issuance
+pub async fn get_vault_nonces(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+) -> Result<Vec<u64>, RcliError> {
+    let vault_spends = get_vault_spends(client, launcher_id).await?;
+    get_nonces_from_vault_spends(client, ctx, launcher_id, &vault_spends).await
+}
+
+// Same as `get_vault_nonces`, for already-fetched vault spends
+pub async fn get_nonces_from_vault_spends(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    vault_spends: &[(CoinRecord, CoinSpend)],
+) -> Result<Vec<u64>, RcliError> {
+    let mut nonces = Vec::new();
+
+    for (_, vault_spend) in vault_spends {
+        // Issuances are approved with a coin-coin message that has delta = 0
+        //   as its contents, sent to the eve CAT
+        for receiver_coin_id in get_sent_messages(ctx, vault_spend)?
+            .into_iter()
+            .filter(|(message, _)| message.is_empty())
+            .map(|(_, receiver_coin_id)| receiver_coin_id)
+        {
+            let Some(coin_record) = client
+                .get_coin_record_by_name(receiver_coin_id)
+                .await?
+                .coin_record
+            else {
+                continue;
+            };
+            let Some(eve_spend) = client
+                .get_puzzle_and_solution(receiver_coin_id, Some(coin_record.spent_block_index))
+                .await?
+                .coin_solution
+            else {
+                continue;
+            };
+
+            let Some(tail_args) = parse_eve_cat_tail(ctx, &eve_spend)? else {
+                continue;
+            };
+            if tail_args != EverythingWithSingletonTailArgs::new(launcher_id, tail_args.nonce) {
+                continue;
+            }
+
+            if !nonces.contains(&tail_args.nonce) {
+                nonces.push(tail_args.nonce);
+            }
+        }
+    }
+
+    nonces.sort_unstable();
+    Ok(nonces)
+}
+
+// Extracts the everything_with_singleton TAIL run by an eve rCAT spend, if any
+pub fn parse_eve_cat_tail(
+    ctx: &mut SpendContext,
+    eve_spend: &CoinSpend,
+) -> Result<Option<EverythingWithSingletonTailArgs>, RcliError> {
+    let puzzle = ctx.alloc(&eve_spend.puzzle_reveal)?;
+    let puzzle = Puzzle::parse(ctx, puzzle);
+    let solution = ctx.alloc(&eve_spend.solution)?;
+
+    let Some(cat_layer) = CatLayer::<Puzzle>::parse_puzzle(ctx, puzzle)? else {
+        return Ok(None);
+    };
+    if RevocationLayer::parse_puzzle(ctx, cat_layer.inner_puzzle)?.is_none() {
+        return Ok(None);
+    }
+
+    let cat_solution = CatLayer::<Puzzle>::parse_solution(ctx, solution)?;
+    let RevocationSolution {
+        puzzle: inner_puzzle,
+        solution: inner_solution,
+        ..
+    } = RevocationLayer::parse_solution(ctx, cat_solution.inner_puzzle_solution)?;
+
+    let output = ctx.run(inner_puzzle, inner_solution)?;
+    let conditions = ctx.extract::<Vec<Condition<NodePtr>>>(output)?;
+
+    for condition in conditions {
+        let Condition::RunCatTail(run_cat_tail) = condition else {
+            continue;
+        };
+
+        let tail = Puzzle::parse(ctx, run_cat_tail.program);
+        let Some(curried_tail) = tail.as_curried() else {
+            continue;
+        };
+        if curried_tail.mod_hash != EVERYTHING_WITH_SINGLETON_TAIL_HASH {
+            continue;
+        }
+
+        let tail_args = ctx.extract::<EverythingWithSingletonTailArgs>(curried_tail.args)?;
+        if Bytes32::from(tail_args.curry_tree_hash()) != cat_layer.asset_id {
+            continue;
+        }
+
+        return Ok(Some(tail_args));
+    }
+
+    Ok(None)
+}
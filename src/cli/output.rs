@@ -0,0 +1,176 @@
+use chia::protocol::Bytes32;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::sync::{Mutex, OnceLock};
+
+use crate::{get_rcat_asset_id, get_rcat_hidden_puzzle_hash};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    // Human-readable progress on stdout
+    #[default]
+    Text,
+    // A single JSON object on stdout; progress goes to stderr
+    Json,
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+static COMMAND_OUTPUT: Mutex<CommandOutput> = Mutex::new(CommandOutput::new());
+
+pub fn set_output_format(format: OutputFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+pub fn is_json_output() -> bool {
+    OUTPUT_FORMAT.get() == Some(&OutputFormat::Json)
+}
+
+// Prints human-readable progress - to stderr in JSON mode, so stdout only
+//  contains the command's output object
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if $crate::is_json_output() {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+// What a command did, printed as JSON once it's done (in JSON mode)
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandOutput {
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub launcher_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hidden_puzzle_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub singleton_struct_hash: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub puzzle_hashes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub bundle_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub coin_ids: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spent: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CommandOutput {
+    const fn new() -> Self {
+        Self {
+            status: String::new(),
+            launcher_id: None,
+            nonce: None,
+            asset_id: None,
+            hidden_puzzle_hash: None,
+            singleton_struct_hash: None,
+            puzzle_hashes: Vec::new(),
+            bundle_ids: Vec::new(),
+            coin_ids: Vec::new(),
+            spent: None,
+            owner_address: None,
+            file: None,
+            error: None,
+        }
+    }
+}
+
+fn format_bytes32(value: Bytes32) -> String {
+    format!("0x{}", hex::encode(value))
+}
+
+fn update_output(f: impl FnOnce(&mut CommandOutput)) {
+    f(&mut COMMAND_OUTPUT.lock().unwrap());
+}
+
+pub fn record_status(status: &str) {
+    update_output(|output| output.status = status.to_string());
+}
+
+pub fn record_launcher_id(launcher_id: Bytes32) {
+    update_output(|output| output.launcher_id = Some(format_bytes32(launcher_id)));
+}
+
+// Records the launcher id, nonce, asset id and hidden puzzle hash of the rCAT
+pub fn record_rcat(launcher_id: Bytes32, nonce: u64) {
+    update_output(|output| {
+        output.launcher_id = Some(format_bytes32(launcher_id));
+        output.nonce = Some(nonce);
+        output.asset_id = Some(format_bytes32(get_rcat_asset_id(launcher_id, nonce)));
+        output.hidden_puzzle_hash = Some(format_bytes32(get_rcat_hidden_puzzle_hash(launcher_id)));
+    });
+}
+
+pub fn record_singleton_struct_hash(singleton_struct_hash: Bytes32) {
+    update_output(|output| {
+        output.singleton_struct_hash = Some(format_bytes32(singleton_struct_hash))
+    });
+}
+
+pub fn record_puzzle_hash(puzzle_hash: Bytes32) {
+    update_output(|output| output.puzzle_hashes.push(format_bytes32(puzzle_hash)));
+}
+
+pub fn record_bundle_id(bundle_id: Bytes32) {
+    update_output(|output| output.bundle_ids.push(format_bytes32(bundle_id)));
+}
+
+pub fn record_coin_ids(coin_ids: impl IntoIterator<Item = Bytes32>) {
+    update_output(|output| {
+        output
+            .coin_ids
+            .extend(coin_ids.into_iter().map(format_bytes32))
+    });
+}
+
+// Records a CAT that may not be an rCAT of a known vault
+pub fn record_cat(asset_id: Bytes32, hidden_puzzle_hash: Option<Bytes32>) {
+    update_output(|output| {
+        output.asset_id = Some(format_bytes32(asset_id));
+        output.hidden_puzzle_hash = hidden_puzzle_hash.map(format_bytes32);
+    });
+}
+
+pub fn record_coin_state(spent: bool, owner_address: Option<String>) {
+    update_output(|output| {
+        output.spent = Some(spent);
+        output.owner_address = owner_address;
+    });
+}
+
+pub fn record_file(file: &str) {
+    update_output(|output| output.file = Some(file.to_string()));
+}
+
+// Prints the recorded output (JSON mode only); `error` is the command's error, if any
+pub fn print_command_output(error: Option<String>) {
+    if !is_json_output() {
+        return;
+    }
+
+    let mut output = COMMAND_OUTPUT.lock().unwrap().clone();
+    if let Some(error) = error {
+        output.status = "error".to_string();
+        output.error = Some(error);
+    } else if output.status.is_empty() {
+        output.status = "ok".to_string();
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string(&output).unwrap_or_else(|_| "{}".to_string())
+    );
+}
@@ -1,22 +1,21 @@
 use chia_wallet_sdk::{prelude::ToTreeHash, utils::Address};
-use slot_machine::{CliError, SageClient};
 
-use crate::get_first_address;
+use crate::{Network, RcliError, WalletSigner, get_first_address, progress};
 
-pub async fn cli_ping() -> Result<(), CliError> {
-    let wallet = SageClient::new()?;
+pub async fn cli_ping() -> Result<(), RcliError> {
+    let wallet = WalletSigner::new(&Network::mainnet())?;
 
     let layer = get_first_address(&wallet).await?;
 
-    println!(
+    progress!(
         "Connected wallet first address (mainnet): {}",
         Address::new(layer.tree_hash().into(), "xch".to_string()).encode()?
     );
-    println!(
+    progress!(
         "Connected wallet first address (testnet): {}",
         Address::new(layer.tree_hash().into(), "txch".to_string()).encode()?
     );
-    println!("PONG!");
+    progress!("PONG!");
 
     Ok(())
 }
@@ -0,0 +1,195 @@
+use chia::protocol::Bytes32;
+use chia_wallet_sdk::utils::Address;
+use csv::Writer;
+use serde::Serialize;
+use slot_machine::CliError;
+use std::fs;
+
+use crate::{Network, RcliError, progress, record_file};
+
+#[derive(Debug, Clone)]
+pub struct RevokedCoinReport {
+    pub coin_id: Bytes32,
+    pub owner_puzzle_hash: Bytes32,
+    pub original_amount: u64,
+    pub revoked_amount: u64,
+    // Coin holding the amount the owner keeps, if any
+    pub refund_coin_id: Option<Bytes32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RevocationTransactionReport {
+    pub transaction_id: Bytes32,
+    pub vault_coin_id: Bytes32,
+    pub fee: u64,
+    // None if the revoked amount was melted
+    pub destination: Option<Bytes32>,
+    pub coins: Vec<RevokedCoinReport>,
+}
+
+// Record of all transactions confirmed during a revoke/revoke-bulk run
+#[derive(Debug, Clone)]
+pub struct RevocationReport {
+    pub launcher_id: Bytes32,
+    pub nonce: u64,
+    pub asset_id: Bytes32,
+    pub network: Network,
+    pub transactions: Vec<RevocationTransactionReport>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RevocationReportFile {
+    launcher_id: String,
+    nonce: u64,
+    asset_id: String,
+    network: String,
+    transactions: Vec<RevocationTransactionFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RevocationTransactionFile {
+    transaction_id: String,
+    vault_coin_id: String,
+    fee: u64,
+    burned: bool,
+    destination_puzzle_hash: Option<String>,
+    destination_address: Option<String>,
+    coins: Vec<RevokedCoinFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct RevokedCoinFile {
+    coin_id: String,
+    owner_puzzle_hash: String,
+    owner_address: String,
+    original_amount: u64,
+    revoked_amount: u64,
+    refund_coin_id: Option<String>,
+}
+
+// CSV reports have one row per revoked coin; transaction fields are repeated
+#[derive(Debug, Clone, Serialize)]
+struct RevokedCoinRow {
+    transaction_id: String,
+    vault_coin_id: String,
+    fee: u64,
+    burned: bool,
+    destination_puzzle_hash: String,
+    destination_address: String,
+    coin_id: String,
+    owner_puzzle_hash: String,
+    owner_address: String,
+    original_amount: u64,
+    revoked_amount: u64,
+    refund_coin_id: String,
+}
+
+impl RevocationReport {
+    pub fn new(launcher_id: Bytes32, nonce: u64, asset_id: Bytes32, network: Network) -> Self {
+        Self {
+            launcher_id,
+            nonce,
+            asset_id,
+            network,
+            transactions: vec![],
+        }
+    }
+
+    // Checks the report can be saved before anything is spent
+    pub fn check_path(path: &str) -> Result<(), RcliError> {
+        if path.ends_with(".json") || path.ends_with(".csv") {
+            Ok(())
+        } else {
+            Err(RcliError::Custom(format!(
+                "Report file '{}' must end with .json or .csv",
+                path
+            )))
+        }
+    }
+
+    // Amounts are in mojos (1 rCAT = 1000 mojos; 1 XCH = 10^12 mojos)
+    pub fn save(&self, path: &str) -> Result<(), RcliError> {
+        Self::check_path(path)?;
+
+        let file = self.to_file()?;
+        if path.ends_with(".json") {
+            let contents = serde_json::to_string_pretty(&file)
+                .map_err(|err| RcliError::Custom(err.to_string()))?;
+            fs::write(path, contents)?;
+        } else {
+            let mut writer = Writer::from_path(path).map_err(CliError::Csv)?;
+            for tx in file.transactions {
+                let destination_puzzle_hash = tx.destination_puzzle_hash.unwrap_or_default();
+                let destination_address = tx.destination_address.unwrap_or_default();
+
+                for coin in tx.coins {
+                    writer
+                        .serialize(RevokedCoinRow {
+                            transaction_id: tx.transaction_id.clone(),
+                            vault_coin_id: tx.vault_coin_id.clone(),
+                            fee: tx.fee,
+                            burned: tx.burned,
+                            destination_puzzle_hash: destination_puzzle_hash.clone(),
+                            destination_address: destination_address.clone(),
+                            coin_id: coin.coin_id,
+                            owner_puzzle_hash: coin.owner_puzzle_hash,
+                            owner_address: coin.owner_address,
+                            original_amount: coin.original_amount,
+                            revoked_amount: coin.revoked_amount,
+                            refund_coin_id: coin.refund_coin_id.unwrap_or_default(),
+                        })
+                        .map_err(CliError::Csv)?;
+                }
+            }
+            writer.flush()?;
+        }
+
+        progress!("Revocation report saved to '{}'", path);
+        record_file(path);
+        Ok(())
+    }
+
+    fn to_file(&self) -> Result<RevocationReportFile, RcliError> {
+        let prefix = self.network.prefix();
+
+        let mut transactions = Vec::with_capacity(self.transactions.len());
+        for tx in &self.transactions {
+            let mut coins = Vec::with_capacity(tx.coins.len());
+            for coin in &tx.coins {
+                coins.push(RevokedCoinFile {
+                    coin_id: format!("0x{}", hex::encode(coin.coin_id)),
+                    owner_puzzle_hash: format!("0x{}", hex::encode(coin.owner_puzzle_hash)),
+                    owner_address: Address::new(coin.owner_puzzle_hash, prefix.clone()).encode()?,
+                    original_amount: coin.original_amount,
+                    revoked_amount: coin.revoked_amount,
+                    refund_coin_id: coin
+                        .refund_coin_id
+                        .map(|coin_id| format!("0x{}", hex::encode(coin_id))),
+                });
+            }
+
+            transactions.push(RevocationTransactionFile {
+                transaction_id: format!("0x{}", hex::encode(tx.transaction_id)),
+                vault_coin_id: format!("0x{}", hex::encode(tx.vault_coin_id)),
+                fee: tx.fee,
+                burned: tx.destination.is_none(),
+                destination_puzzle_hash: tx
+                    .destination
+                    .map(|destination| format!("0x{}", hex::encode(destination))),
+                destination_address: tx
+                    .destination
+                    .map(|destination| Address::new(destination, prefix.clone()).encode())
+                    .transpose()?,
+                coins,
+            });
+        }
+
+        Ok(RevocationReportFile {
+            launcher_id: format!("0x{}", hex::encode(self.launcher_id)),
+            nonce: self.nonce,
+            asset_id: format!("0x{}", hex::encode(self.asset_id)),
+            network: self.network.name().to_string(),
+            transactions,
+        })
+    }
+}
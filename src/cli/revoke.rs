@@ -1,62 +1,90 @@
-use chia::{clvm_utils::ToTreeHash, protocol::Bytes32};
-use chia_puzzle_types::singleton::SingletonStruct;
-use chia_wallet_sdk::{
-    coinset::ChiaRpcClient,
-    types::{Mod, puzzles::P2DelegatedBySingletonLayerArgs},
-};
-use slot_machine::{CliError, get_coinset_client, hex_string_to_bytes32, parse_amount};
+use chia::protocol::Bytes32;
+use chia_wallet_sdk::coinset::ChiaRpcClient;
+use slot_machine::{CliError, hex_string_to_bytes32, parse_amount};
 
-use crate::{EverythingWithSingletonTailArgs, revoke_coins};
+use crate::{
+    Network, RcliError, RevocationReport, VaultSpendOptions, get_rcat_asset_id,
+    get_rcat_hidden_puzzle_hash, get_rpc_client, parse_destination, progress, record_rcat,
+    revoke_coins,
+};
 
+#[allow(clippy::too_many_arguments)]
 pub async fn cli_revoke(
     launcher_id_str: String,
+    nonce: u64,
     percentage: u8,
     coin_ids_str: String,
     fee_str: String,
-    testnet11: bool,
-) -> Result<(), CliError> {
+    burn: bool,
+    destination: Option<String>,
+    report: Option<String>,
+    export: Option<String>,
+    signers: Option<String>,
+    dry_run: bool,
+    network: &Network,
+) -> Result<(), RcliError> {
     let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
     let fee = parse_amount(&fee_str, false)?;
+    let destination = destination
+        .map(|destination| parse_destination(&destination, launcher_id, network))
+        .transpose()?;
+    let spend_options = VaultSpendOptions::parse(export, signers, dry_run)?;
+    if let Some(report) = &report {
+        RevocationReport::check_path(report)?;
+    }
     let coin_ids = coin_ids_str
         .replace("0x", "")
         .split(',')
         .map(hex_string_to_bytes32)
         .collect::<Result<Vec<Bytes32>, CliError>>()?;
 
-    let tail_args = EverythingWithSingletonTailArgs::new(launcher_id, 0);
-    let asset_id: Bytes32 = tail_args.curry_tree_hash().into();
-    println!("rCAT asset id: {:}", hex::encode(asset_id));
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    record_rcat(launcher_id, nonce);
+    progress!(
+        "rCAT asset id (nonce {}): {:}",
+        nonce,
+        hex::encode(asset_id)
+    );
 
-    let singleton_struct_hash: Bytes32 = SingletonStruct::new(launcher_id).tree_hash().into();
-    let hidden_puzzle_hash: Bytes32 =
-        P2DelegatedBySingletonLayerArgs::curry_tree_hash(singleton_struct_hash, 0).into();
-    println!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+    progress!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
 
-    println!("Fetching rCAT coin records...");
+    progress!("Fetching rCAT coin records...");
     let coin_ids_len = coin_ids.len();
-    let client = get_coinset_client(testnet11);
+    let client = get_rpc_client(network)?;
     let Some(coin_records) = client
         .get_coin_records_by_names(coin_ids, None, None, Some(true))
         .await?
         .coin_records
     else {
-        return Err(CliError::Custom("Error fetching coin records".to_string()));
+        return Err(RcliError::Custom("Error fetching coin records".to_string()));
     };
     if coin_records.len() != coin_ids_len {
-        return Err(CliError::Custom(
+        return Err(RcliError::Custom(
             "Could not find one or more rCAT coins on-chain".to_string(),
         ));
     }
 
-    revoke_coins(
+    let transaction_report = revoke_coins(
         launcher_id,
-        testnet11,
+        nonce,
+        network,
         percentage,
         fee,
-        asset_id,
-        hidden_puzzle_hash,
+        burn,
+        destination,
+        &spend_options,
         &client,
         coin_records,
     )
-    .await
+    .await?;
+
+    if let (Some(report), Some(transaction_report)) = (report, transaction_report) {
+        let mut revocation_report =
+            RevocationReport::new(launcher_id, nonce, asset_id, network.clone());
+        revocation_report.transactions.push(transaction_report);
+        revocation_report.save(&report)?;
+    }
+
+    Ok(())
 }
@@ -1,63 +1,303 @@
-use chia::{clvm_utils::ToTreeHash, protocol::Bytes32};
-use chia_puzzle_types::singleton::SingletonStruct;
+use chia::{bls::PublicKey, protocol::Bytes32};
 use chia_wallet_sdk::{
-    coinset::ChiaRpcClient,
-    types::{Mod, puzzles::P2DelegatedBySingletonLayerArgs},
+    coinset::{ChiaRpcClient, CoinRecord},
+    driver::{Cat, MedievalVault, SpendContext},
+    prelude::ToTreeHash,
     utils::Address,
 };
 use csv::ReaderBuilder;
 use hex::FromHex;
 use serde::Deserialize;
-use slot_machine::{CliError, get_coinset_client, hex_string_to_bytes32, parse_amount};
+use slot_machine::{CliError, hex_string_to_bytes32, parse_amount};
 use std::{fs::File, path::Path};
 
-use crate::{EverythingWithSingletonTailArgs, revoke_coins};
+use crate::{
+    Network, RcliError, RevocationBatchStatus, RevocationReport, RevokeBulkState, RpcClient,
+    VaultSpendOptions, WalletSigner, get_first_address, get_rcat_asset_id,
+    get_rcat_hidden_puzzle_hash, get_rpc_client, get_spends_cost, parse_destination,
+    parse_rcat_coins, progress, record_rcat, revoke_cats, select_vault_signers, spend_revocation,
+    split_by_cost, sync_vault, wait_for_confirmation,
+};
+
+// Revocation transactions also contain the security coin and funding spends, so
+//  the (measured) cost of the rCAT and vault spends is kept a bit under the
+//  mempool's per-transaction limit (half of the block cost limit)
+pub const MAX_REVOCATION_SPENDS_COST: u64 = 5_000_000_000;
 
 #[allow(clippy::too_many_arguments)]
 pub async fn cli_revoke_bulk(
     launcher_id_str: String,
-    csv: String,
+    nonce: u64,
+    csv: Option<String>,
     percentage: u8,
     min_coins: usize,
     max_coins: usize,
     min_coin_amount_str: String,
-    exclude_addresses: String,
+    exclude_addresses: Option<String>,
     fee_str: String,
-    testnet11: bool,
-) -> Result<(), CliError> {
+    burn: bool,
+    destination: Option<String>,
+    state_file: String,
+    resume: bool,
+    report: Option<String>,
+    export: Option<String>,
+    signers: Option<String>,
+    dry_run: bool,
+    network: &Network,
+) -> Result<(), RcliError> {
     let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
     let min_coin_amount = parse_amount(&min_coin_amount_str, true)?;
     let fee = parse_amount(&fee_str, false)?;
+    let destination = destination
+        .map(|destination| parse_destination(&destination, launcher_id, network))
+        .transpose()?;
+    let spend_options = VaultSpendOptions::parse(export, signers, dry_run)?;
+    if let Some(report) = &report {
+        RevocationReport::check_path(report)?;
+    }
+    // Exported and simulated runs don't revoke anything, so there's nothing to record
+    let save_state = !spend_options.dry_run && spend_options.export.is_none();
+
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    record_rcat(launcher_id, nonce);
+    progress!(
+        "rCAT asset id (nonce {}): {:}",
+        nonce,
+        hex::encode(asset_id)
+    );
+
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+    progress!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
+
+    let client = get_rpc_client(network)?;
+    let wallet = WalletSigner::new(network)?;
+
+    let (mut state, mut coin_records) = if resume {
+        progress!("Resuming from '{}'...", state_file);
+        let mut state = RevokeBulkState::load(&state_file)?;
+        if state.launcher_id != launcher_id
+            || state.nonce != nonce
+            || state.network.genesis_challenge != network.genesis_challenge
+        {
+            return Err(RcliError::Custom(format!(
+                "State file '{}' belongs to a different vault, nonce or network",
+                state_file
+            )));
+        }
+        progress!(
+            "Using saved settings: percentage {}, {}",
+            state.percentage,
+            if state.burn {
+                "burning revoked rCATs".to_string()
+            } else {
+                format!(
+                    "sending revoked rCATs to {}",
+                    Address::new(state.destination, network.prefix()).encode()?
+                )
+            }
+        );
+
+        let coin_records = get_unrevoked_coin_records(&client, &mut state).await?;
+        if save_state {
+            state.save(&state_file)?;
+            // Lists the transactions confirmed before the run was interrupted
+            if let Some(report) = &report {
+                state.revocation_report(asset_id).save(report)?;
+            }
+        }
+
+        (state, coin_records)
+    } else {
+        if save_state && Path::new(&state_file).exists() {
+            return Err(RcliError::Custom(format!(
+                "State file '{}' already exists - use --resume to continue that run, or remove the file",
+                state_file
+            )));
+        }
+        let (Some(csv), Some(exclude_addresses)) = (csv, exclude_addresses) else {
+            return Err(RcliError::Custom(
+                "--csv and --exclude-addresses are required unless resuming".to_string(),
+            ));
+        };
+
+        progress!("Getting holders from '{}'...", csv);
+        let holders = load_holders_csv(csv)?;
+        progress!("Got {} holders.", holders.len());
+
+        progress!("Fetching rCAT coin records...");
+
+        let mut excluded_puzzle_hashes = Vec::new();
+        for address in exclude_addresses.split(',') {
+            progress!("Excluding address: {}", address);
+            let puzzle_hash = Address::decode(address)?.puzzle_hash;
+            excluded_puzzle_hashes.push(puzzle_hash);
+        }
+
+        let mut coin_names = Vec::new();
+        for holder in holders {
+            if excluded_puzzle_hashes.contains(&holder.puzzle_hash) {
+                continue;
+            }
+            coin_names.push(holder.coin_name);
+        }
+
+        let mut coin_records = get_unspent_coin_records(&client, coin_names).await?;
+        coin_records = coin_records
+            .into_iter()
+            .filter(|cr| cr.coin.amount >= min_coin_amount)
+            .collect::<Vec<_>>();
+        coin_records.sort_unstable_by(|a, b| b.coin.amount.cmp(&a.coin.amount));
 
-    let tail_args = EverythingWithSingletonTailArgs::new(launcher_id, 0);
-    let asset_id: Bytes32 = tail_args.curry_tree_hash().into();
-    println!("rCAT asset id: {:}", hex::encode(asset_id));
+        if coin_records.len() < min_coins {
+            return Err(RcliError::InsufficientCoins(format!(
+                "not enough coins to revoke: {} < {}",
+                coin_records.len(),
+                min_coins
+            )));
+        }
+        if coin_records.len() > max_coins {
+            coin_records.truncate(max_coins);
+        }
 
-    let singleton_struct_hash: Bytes32 = SingletonStruct::new(launcher_id).tree_hash().into();
-    let hidden_puzzle_hash: Bytes32 =
-        P2DelegatedBySingletonLayerArgs::curry_tree_hash(singleton_struct_hash, 0).into();
-    println!("Hidden puzzle hash: {:}", hex::encode(hidden_puzzle_hash));
+        // Resolved once so all transactions send revoked rCATs to the same place
+        let destination: Bytes32 = if let Some(destination) = destination {
+            destination
+        } else {
+            get_first_address(&wallet).await?.tree_hash().into()
+        };
 
-    println!("Getting holders from '{}'...", csv);
-    let holders = load_holders_csv(csv)?;
-    println!("Got {} holders.", holders.len());
+        let state = RevokeBulkState {
+            launcher_id,
+            nonce,
+            network: network.clone(),
+            percentage,
+            burn,
+            destination,
+            batches: vec![],
+        };
 
-    println!("Fetching rCAT coin records...");
-    let client = get_coinset_client(testnet11);
+        (state, coin_records)
+    };
 
-    let mut excluded_puzzle_hashes = Vec::new();
-    for address in exclude_addresses.split(',') {
-        println!("Excluding address: {}", address);
-        let puzzle_hash = Address::decode(address)?.puzzle_hash;
-        excluded_puzzle_hashes.push(puzzle_hash);
+    if coin_records.is_empty() {
+        progress!("Nothing left to revoke.");
+        return Ok(());
     }
+    coin_records.sort_unstable_by(|a, b| b.coin.amount.cmp(&a.coin.amount));
 
-    let mut coin_names = Vec::new();
-    for holder in holders {
-        if excluded_puzzle_hashes.contains(&holder.puzzle_hash) {
-            continue;
+    let mut ctx = SpendContext::new();
+    let cats = parse_rcat_coins(
+        &client,
+        &mut ctx,
+        coin_records,
+        asset_id,
+        hidden_puzzle_hash,
+    )
+    .await?;
+
+    let vault = sync_vault(&client, &mut ctx, launcher_id).await?;
+    let signers = select_vault_signers(&wallet, &vault, &spend_options).await?;
+
+    progress!("Measuring revocation cost...");
+    let batches = split_revocation_batches(
+        launcher_id,
+        nonce,
+        network,
+        &vault,
+        &signers,
+        cats,
+        state.percentage,
+        state.burn,
+        state.destination,
+        MAX_REVOCATION_SPENDS_COST,
+    )?;
+
+    let batch_count = batches.len();
+    if batch_count > 1 && spend_options.export.is_some() {
+        // Each transaction spends the vault coin created by the previous one
+        return Err(RcliError::Custom(format!(
+            "Revocation requires {} transactions, but only a single one can be exported",
+            batch_count
+        )));
+    }
+    if batch_count > 1 {
+        progress!(
+            "Revocation will be split into {} transactions (fee is paid for each one).",
+            batch_count
+        );
+    }
+    if batch_count > 1 && spend_options.dry_run {
+        progress!(
+            "Dry run: only the first transaction will be simulated, as the others spend the vault coin it creates."
+        );
+    }
+
+    let first_batch_index = state.plan_batches(&batches);
+    if save_state {
+        state.save(&state_file)?;
+        progress!("Progress will be saved to '{}'", state_file);
+    }
+
+    for (i, batch) in batches.into_iter().enumerate() {
+        progress!("Revocation {}/{}...", i + 1, batch_count);
+
+        let batch_index = first_batch_index + i;
+        let percentage = state.percentage;
+        let burn = state.burn;
+        let destination = state.destination;
+        revoke_cats(
+            launcher_id,
+            nonce,
+            network,
+            percentage,
+            fee,
+            burn,
+            Some(destination),
+            &spend_options,
+            &client,
+            batch,
+            |transaction_report| {
+                state.set_submitted(batch_index, transaction_report);
+                state.save(&state_file)
+            },
+        )
+        .await?;
+
+        if spend_options.dry_run {
+            break;
+        }
+
+        if save_state {
+            state.set_status(batch_index, RevocationBatchStatus::Confirmed, None);
+            state.save(&state_file)?;
+
+            // Saved after each confirmed transaction (with those of previous runs),
+            //  so it's complete even if the run fails
+            if let Some(report) = &report {
+                state.revocation_report(asset_id).save(report)?;
+            }
         }
-        coin_names.push(holder.coin_name);
+    }
+
+    Ok(())
+}
+
+// Returns the records of the given coins that are still unspent
+async fn get_unspent_coin_records(
+    client: &RpcClient,
+    coin_ids: Vec<Bytes32>,
+) -> Result<Vec<CoinRecord>, RcliError> {
+    // Full nodes handle the batched call fine
+    if client.is_full_node() {
+        let Some(coin_records) = client
+            .get_coin_records_by_names(coin_ids, None, None, Some(false))
+            .await?
+            .coin_records
+        else {
+            return Err(RcliError::Custom("No coin records found".to_string()));
+        };
+
+        return Ok(coin_records);
     }
 
     // Temp fix until coinset fixes their stuff
@@ -66,11 +306,11 @@ pub async fn cli_revoke_bulk(
     //     .await?
     //     .coin_records
     // else {
-    //     return Err(CliError::Custom("No coin records found".to_string()));
+    //     return Err(RcliError::Custom("No coin records found".to_string()));
     // };
     let mut coin_records = Vec::new();
-    for coin_name in coin_names {
-        let Some(record) = client.get_coin_record_by_name(coin_name).await?.coin_record else {
+    for coin_id in coin_ids {
+        let Some(record) = client.get_coin_record_by_name(coin_id).await?.coin_record else {
             continue;
         };
 
@@ -82,34 +322,125 @@ pub async fn cli_revoke_bulk(
     }
     // end temp fix
 
-    coin_records = coin_records
-        .into_iter()
-        .filter(|cr| !cr.spent && cr.coin.amount >= min_coin_amount)
-        .collect::<Vec<_>>();
-    coin_records.sort_unstable_by(|a, b| b.coin.amount.cmp(&a.coin.amount));
+    Ok(coin_records)
+}
 
-    if coin_records.len() < min_coins {
-        return Err(CliError::Custom(format!(
-            "Not enough coins to revoke: {} < {}",
-            coin_records.len(),
-            min_coins
-        )));
-    }
-    if coin_records.len() > max_coins {
-        coin_records.truncate(max_coins);
+// Re-checks all batches that were not confirmed when the run stopped
+// Batches still in the mempool are waited for; coins of all other unconfirmed
+//  batches that are still unspent are returned so they can be planned again
+async fn get_unrevoked_coin_records(
+    client: &RpcClient,
+    state: &mut RevokeBulkState,
+) -> Result<Vec<CoinRecord>, RcliError> {
+    let mut coin_records = Vec::new();
+
+    for batch in state
+        .batches
+        .iter()
+        .filter(|batch| batch.status != RevocationBatchStatus::Confirmed)
+    {
+        if let Some(spend_bundle_id) = batch.spend_bundle_id
+            && client
+                .get_mempool_item_by_tx_id(spend_bundle_id)
+                .await?
+                .mempool_item
+                .is_some()
+        {
+            progress!(
+                "Transaction {} is still in the mempool; waiting for it to be confirmed...",
+                hex::encode(spend_bundle_id)
+            );
+            wait_for_confirmation(client, batch.coin_ids[0]).await?;
+        }
+
+        let unspent = get_unspent_coin_records(client, batch.coin_ids.clone()).await?;
+        if unspent.is_empty() {
+            progress!(
+                "All {} coins of a previous batch were revoked.",
+                batch.coin_ids.len()
+            );
+            continue;
+        }
+
+        progress!(
+            "{} of {} coins from a previous batch still need to be revoked.",
+            unspent.len(),
+            batch.coin_ids.len()
+        );
+        coin_records.extend(unspent);
     }
 
-    revoke_coins(
+    let unspent_coin_ids = coin_records
+        .iter()
+        .map(|coin_record| coin_record.coin.coin_id())
+        .collect::<Vec<_>>();
+    state.resume(&unspent_coin_ids);
+
+    Ok(coin_records)
+}
+
+// Returns the CLVM cost of the rCAT and vault spends needed to revoke the given coins
+#[allow(clippy::too_many_arguments)]
+pub fn get_revocation_cost(
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: &MedievalVault,
+    signers: &[PublicKey],
+    cats: &[Cat],
+    percentage: u8,
+    burn: bool,
+    destination: Bytes32,
+) -> Result<u64, RcliError> {
+    let mut ctx = SpendContext::new();
+    spend_revocation(
+        &mut ctx,
         launcher_id,
-        testnet11,
+        nonce,
+        network,
+        vault.clone(),
+        signers,
+        cats.to_vec(),
         percentage,
-        fee,
-        asset_id,
-        hidden_puzzle_hash,
-        &client,
-        coin_records,
+        burn,
+        destination,
+    )?;
+
+    get_spends_cost(ctx.take(), network)
+}
+
+// Splits the coins into consecutive batches whose spends cost at most `max_cost`
+#[allow(clippy::too_many_arguments)]
+pub fn split_revocation_batches(
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: &MedievalVault,
+    signers: &[PublicKey],
+    cats: Vec<Cat>,
+    percentage: u8,
+    burn: bool,
+    destination: Bytes32,
+    max_cost: u64,
+) -> Result<Vec<Vec<Cat>>, RcliError> {
+    split_by_cost(
+        cats,
+        max_cost,
+        |cats| {
+            get_revocation_cost(
+                launcher_id,
+                nonce,
+                network,
+                vault,
+                signers,
+                cats,
+                percentage,
+                burn,
+                destination,
+            )
+        },
+        |cat| format!("Revoking coin 0x{}", hex::encode(cat.coin.coin_id())),
     )
-    .await
 }
 
 fn serde_hex_string_to_bytes32<'de, D>(deserializer: D) -> Result<Bytes32, D::Error>
@@ -130,7 +461,7 @@ pub struct HolderCoinRecord {
     pub amount: u64,
 }
 
-pub fn load_holders_csv<P: AsRef<Path>>(path: P) -> Result<Vec<HolderCoinRecord>, CliError> {
+pub fn load_holders_csv<P: AsRef<Path>>(path: P) -> Result<Vec<HolderCoinRecord>, RcliError> {
     let file = File::open(path)?;
     let mut rdr = ReaderBuilder::new().has_headers(true).from_reader(file);
 
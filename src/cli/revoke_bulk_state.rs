@@ -0,0 +1,262 @@
+use chia::protocol::Bytes32;
+use chia_wallet_sdk::driver::Cat;
+use serde::{Deserialize, Serialize};
+use slot_machine::{CliError, hex_string_to_bytes32};
+use std::fs;
+
+use crate::{
+    Network, NetworkFile, RcliError, RevocationReport, RevocationTransactionReport,
+    RevokedCoinReport,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RevocationBatchStatus {
+    Planned,
+    // Pushed to the mempool, but not confirmed yet
+    Submitted,
+    Confirmed,
+}
+
+#[derive(Debug, Clone)]
+pub struct RevocationBatch {
+    pub coin_ids: Vec<Bytes32>,
+    pub status: RevocationBatchStatus,
+    pub spend_bundle_id: Option<Bytes32>,
+    // Set when the batch's transaction is submitted, so the --report of a
+    //  resumed run still lists it
+    pub report: Option<RevocationTransactionReport>,
+}
+
+// Progress of a revoke-bulk run, saved after every step so an interrupted
+//  run can be resumed
+// Revocation settings are saved as well - a resumed run always uses them
+#[derive(Debug, Clone)]
+pub struct RevokeBulkState {
+    pub launcher_id: Bytes32,
+    pub nonce: u64,
+    pub network: Network,
+    pub percentage: u8,
+    pub burn: bool,
+    pub destination: Bytes32,
+    pub batches: Vec<RevocationBatch>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RevokeBulkStateFile {
+    launcher_id: String,
+    nonce: u64,
+    network: NetworkFile,
+    percentage: u8,
+    burn: bool,
+    destination: String,
+    batches: Vec<RevocationBatchFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RevocationBatchFile {
+    coin_ids: Vec<String>,
+    status: RevocationBatchStatus,
+    spend_bundle_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    report: Option<RevocationTransactionFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RevocationTransactionFile {
+    transaction_id: String,
+    vault_coin_id: String,
+    fee: u64,
+    destination: Option<String>,
+    coins: Vec<RevokedCoinFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RevokedCoinFile {
+    coin_id: String,
+    owner_puzzle_hash: String,
+    original_amount: u64,
+    revoked_amount: u64,
+    refund_coin_id: Option<String>,
+}
+
+impl RevocationTransactionFile {
+    fn new(report: &RevocationTransactionReport) -> Self {
+        Self {
+            transaction_id: hex::encode(report.transaction_id),
+            vault_coin_id: hex::encode(report.vault_coin_id),
+            fee: report.fee,
+            destination: report.destination.map(hex::encode),
+            coins: report
+                .coins
+                .iter()
+                .map(|coin| RevokedCoinFile {
+                    coin_id: hex::encode(coin.coin_id),
+                    owner_puzzle_hash: hex::encode(coin.owner_puzzle_hash),
+                    original_amount: coin.original_amount,
+                    revoked_amount: coin.revoked_amount,
+                    refund_coin_id: coin.refund_coin_id.map(hex::encode),
+                })
+                .collect(),
+        }
+    }
+
+    fn parse(self) -> Result<RevocationTransactionReport, RcliError> {
+        let mut coins = Vec::with_capacity(self.coins.len());
+        for coin in self.coins {
+            coins.push(RevokedCoinReport {
+                coin_id: hex_string_to_bytes32(&coin.coin_id)?,
+                owner_puzzle_hash: hex_string_to_bytes32(&coin.owner_puzzle_hash)?,
+                original_amount: coin.original_amount,
+                revoked_amount: coin.revoked_amount,
+                refund_coin_id: coin
+                    .refund_coin_id
+                    .map(|id| hex_string_to_bytes32(&id))
+                    .transpose()?,
+            });
+        }
+
+        Ok(RevocationTransactionReport {
+            transaction_id: hex_string_to_bytes32(&self.transaction_id)?,
+            vault_coin_id: hex_string_to_bytes32(&self.vault_coin_id)?,
+            fee: self.fee,
+            destination: self
+                .destination
+                .map(|destination| hex_string_to_bytes32(&destination))
+                .transpose()?,
+            coins,
+        })
+    }
+}
+
+impl RevokeBulkState {
+    pub fn set_status(
+        &mut self,
+        batch_index: usize,
+        status: RevocationBatchStatus,
+        spend_bundle_id: Option<Bytes32>,
+    ) {
+        let batch = &mut self.batches[batch_index];
+        batch.status = status;
+        if spend_bundle_id.is_some() {
+            batch.spend_bundle_id = spend_bundle_id;
+        }
+    }
+
+    // Adds a planned batch for each group of coins; returns the index of the first one
+    pub fn plan_batches(&mut self, batches: &[Vec<Cat>]) -> usize {
+        let first_batch_index = self.batches.len();
+        for batch in batches {
+            self.batches.push(RevocationBatch {
+                coin_ids: batch.iter().map(|cat| cat.coin.coin_id()).collect(),
+                status: RevocationBatchStatus::Planned,
+                spend_bundle_id: None,
+                report: None,
+            });
+        }
+
+        first_batch_index
+    }
+
+    // Called when resuming, after batches still in the mempool were confirmed
+    // Unconfirmed batches whose coins were all spent are marked as confirmed;
+    //  the others are dropped, as their unspent coins will be planned again
+    pub fn resume(&mut self, unspent_coin_ids: &[Bytes32]) {
+        for batch in &mut self.batches {
+            if batch.status != RevocationBatchStatus::Confirmed
+                && !batch
+                    .coin_ids
+                    .iter()
+                    .any(|coin_id| unspent_coin_ids.contains(coin_id))
+            {
+                batch.status = RevocationBatchStatus::Confirmed;
+            }
+        }
+
+        self.batches
+            .retain(|batch| batch.status == RevocationBatchStatus::Confirmed);
+    }
+
+    pub fn set_submitted(&mut self, batch_index: usize, report: &RevocationTransactionReport) {
+        self.set_status(
+            batch_index,
+            RevocationBatchStatus::Submitted,
+            Some(report.transaction_id),
+        );
+        self.batches[batch_index].report = Some(report.clone());
+    }
+
+    // Report of all confirmed batches, including the ones of previous runs
+    pub fn revocation_report(&self, asset_id: Bytes32) -> RevocationReport {
+        let mut report =
+            RevocationReport::new(self.launcher_id, self.nonce, asset_id, self.network.clone());
+        report.transactions = self
+            .batches
+            .iter()
+            .filter(|batch| batch.status == RevocationBatchStatus::Confirmed)
+            .filter_map(|batch| batch.report.clone())
+            .collect();
+
+        report
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), RcliError> {
+        let file = RevokeBulkStateFile {
+            launcher_id: hex::encode(self.launcher_id),
+            nonce: self.nonce,
+            network: self.network.to_file(),
+            percentage: self.percentage,
+            burn: self.burn,
+            destination: hex::encode(self.destination),
+            batches: self
+                .batches
+                .iter()
+                .map(|batch| RevocationBatchFile {
+                    coin_ids: batch.coin_ids.iter().map(hex::encode).collect(),
+                    status: batch.status,
+                    spend_bundle_id: batch.spend_bundle_id.map(hex::encode),
+                    report: batch.report.as_ref().map(RevocationTransactionFile::new),
+                })
+                .collect(),
+        };
+
+        let contents = serde_json::to_string_pretty(&file)
+            .map_err(|err| RcliError::Custom(err.to_string()))?;
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, RcliError> {
+        let contents = fs::read_to_string(path)?;
+        let file: RevokeBulkStateFile =
+            serde_json::from_str(&contents).map_err(|err| RcliError::Custom(err.to_string()))?;
+
+        let mut batches = Vec::with_capacity(file.batches.len());
+        for batch in file.batches {
+            batches.push(RevocationBatch {
+                coin_ids: batch
+                    .coin_ids
+                    .iter()
+                    .map(|coin_id| hex_string_to_bytes32(coin_id))
+                    .collect::<Result<Vec<Bytes32>, CliError>>()?,
+                status: batch.status,
+                spend_bundle_id: batch
+                    .spend_bundle_id
+                    .map(|id| hex_string_to_bytes32(&id))
+                    .transpose()?,
+                report: batch.report.map(|report| report.parse()).transpose()?,
+            });
+        }
+
+        Ok(Self {
+            launcher_id: hex_string_to_bytes32(&file.launcher_id)?,
+            nonce: file.nonce,
+            network: Network::from_file(file.network)?,
+            percentage: file.percentage,
+            burn: file.burn,
+            destination: hex_string_to_bytes32(&file.destination)?,
+            batches,
+        })
+    }
+}
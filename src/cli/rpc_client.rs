@@ -0,0 +1,141 @@
+use chia_wallet_sdk::coinset::{ChiaRpcClient, CoinsetClient};
+use reqwest::{Certificate, Client, Identity};
+use serde::{Serialize, de::DeserializeOwned};
+use slot_machine::get_coinset_client;
+use std::{env, fs, path::PathBuf};
+
+use crate::{Network, NetworkKind, RcliError};
+
+// If set, this full node RPC (e.g., https://localhost:8555) is used instead of coinset.org
+//  or the network's --rpc-url
+pub const FULL_NODE_URL_ENV_VAR: &str = "RCLI_FULL_NODE_URL";
+// Client certificate and key used to authenticate to the full node; default to
+//  the node's private_full_node certificates in ~/.chia/mainnet
+pub const FULL_NODE_CERT_ENV_VAR: &str = "RCLI_FULL_NODE_CERT";
+pub const FULL_NODE_KEY_ENV_VAR: &str = "RCLI_FULL_NODE_KEY";
+// CA the full node's certificate is verified against; defaults to the node's
+//  private CA in ~/.chia/mainnet
+pub const FULL_NODE_CA_ENV_VAR: &str = "RCLI_FULL_NODE_CA";
+
+// Talks to a Chia full node's RPC over mutual TLS
+pub struct FullNodeClient {
+    base_url: String,
+    client: Client,
+}
+
+impl FullNodeClient {
+    pub fn new(
+        base_url: &str,
+        ca_path: &PathBuf,
+        cert_path: &PathBuf,
+        key_path: &PathBuf,
+    ) -> Result<Self, RcliError> {
+        let mut pem = fs::read(cert_path)?;
+        pem.extend(fs::read(key_path)?);
+        let ca = Certificate::from_pem(&fs::read(ca_path)?)?;
+
+        // Only the node's private CA is trusted, so the whole chain is still verified;
+        //  the hostname check is skipped because the node's certificate is issued
+        //  for 'chia.net', not for the host it's reached at
+        let client = Client::builder()
+            .use_rustls_tls()
+            .tls_built_in_root_certs(false)
+            .add_root_certificate(ca)
+            .danger_accept_invalid_hostnames(true)
+            .identity(Identity::from_pem(&pem)?)
+            .build()?;
+
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client,
+        })
+    }
+}
+
+impl ChiaRpcClient for FullNodeClient {
+    type Error = reqwest::Error;
+
+    fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    async fn make_post_request<R, B>(&self, endpoint: &str, body: B) -> Result<R, Self::Error>
+    where
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        self.client
+            .post(format!("{}/{}", self.base_url, endpoint))
+            .json(&body)
+            .send()
+            .await?
+            .json::<R>()
+            .await
+    }
+}
+
+// Either coinset.org (default) or a full node, depending on the environment
+pub enum RpcClient {
+    Coinset(CoinsetClient),
+    FullNode(FullNodeClient),
+}
+
+impl RpcClient {
+    pub fn is_full_node(&self) -> bool {
+        matches!(self, Self::FullNode(_))
+    }
+}
+
+impl ChiaRpcClient for RpcClient {
+    type Error = reqwest::Error;
+
+    fn base_url(&self) -> &str {
+        match self {
+            Self::Coinset(client) => client.base_url(),
+            Self::FullNode(client) => client.base_url(),
+        }
+    }
+
+    async fn make_post_request<R, B>(&self, endpoint: &str, body: B) -> Result<R, Self::Error>
+    where
+        B: Serialize,
+        R: DeserializeOwned,
+    {
+        match self {
+            Self::Coinset(client) => client.make_post_request(endpoint, body).await,
+            Self::FullNode(client) => client.make_post_request(endpoint, body).await,
+        }
+    }
+}
+
+pub fn get_rpc_client(network: &Network) -> Result<RpcClient, RcliError> {
+    let Some(url) = env::var(FULL_NODE_URL_ENV_VAR)
+        .ok()
+        .or_else(|| network.rpc_url.clone())
+    else {
+        return match network.kind {
+            NetworkKind::Mainnet => Ok(RpcClient::Coinset(get_coinset_client(false))),
+            NetworkKind::Testnet11 => Ok(RpcClient::Coinset(get_coinset_client(true))),
+            NetworkKind::Custom => Err(RcliError::Custom(format!(
+                "Custom networks need a full node RPC (--rpc-url or {})",
+                FULL_NODE_URL_ENV_VAR
+            ))),
+        };
+    };
+
+    let ssl_dir =
+        PathBuf::from(env::var("HOME").unwrap_or_default()).join(".chia/mainnet/config/ssl");
+    let ca_path = env::var(FULL_NODE_CA_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| ssl_dir.join("ca/private_ca.crt"));
+    let cert_path = env::var(FULL_NODE_CERT_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| ssl_dir.join("full_node/private_full_node.crt"));
+    let key_path = env::var(FULL_NODE_KEY_ENV_VAR)
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| ssl_dir.join("full_node/private_full_node.key"));
+
+    Ok(RpcClient::FullNode(FullNodeClient::new(
+        &url, &ca_path, &cert_path, &key_path,
+    )?))
+}
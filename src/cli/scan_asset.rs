@@ -0,0 +1,577 @@
+use std::collections::HashSet;
+
+use chia::{
+    clvm_utils::{TreeHash, curry_tree_hash},
+    protocol::{Bytes32, CoinSpend},
+};
+use chia_puzzles::SINGLETON_TOP_LAYER_V1_1_HASH;
+use chia_wallet_sdk::{
+    coinset::ChiaRpcClient,
+    driver::{
+        CatLayer, Layer, P2DelegatedBySingletonLayer, Puzzle, RevocationLayer, SingletonLayer,
+        SpendContext,
+    },
+    types::{
+        Condition,
+        puzzles::{P2DelegatedBySingletonLayerArgs, RevocationSolution},
+    },
+};
+use clvmr::NodePtr;
+use slot_machine::hex_string_to_bytes32;
+
+use crate::{
+    EVERYTHING_WITH_SINGLETON_TAIL_HASH, EverythingWithSingletonTailArgs,
+    EverythingWithSingletonTailSolution, Network, RcliError, RpcClient, get_known_vaults,
+    get_rcat_asset_id, get_rcat_holders, get_rpc_client, get_singleton_struct_hash,
+    get_vault_nonces, parse_cat_from_coin_record, progress, record_cat, record_coin_ids,
+    record_launcher_id, record_rcat, record_singleton_struct_hash,
+};
+
+// How many coins are sampled when they're found by crawling a vault's issuances
+pub const SCAN_ASSET_SAMPLE_SIZE: usize = 10;
+
+// What a CAT spend reveals about the asset
+struct CatSpendInfo {
+    asset_id: Bytes32,
+    hidden_puzzle_hash: Option<Bytes32>,
+    // TAIL program and solution, if the spend ran the TAIL (issuance or melt)
+    tail: Option<(NodePtr, NodePtr)>,
+    // Set if the coin was spent through its hidden puzzle (i.e., it was revoked)
+    hidden_puzzle: Option<Puzzle>,
+}
+
+// The asset's TAIL, as revealed by one of the sampled coins' spends
+struct RevealedTail {
+    coin_id: Bytes32,
+    mod_hash: Option<TreeHash>,
+    // Curried args and the singleton inner puzzle hash from the solution
+    everything_with_singleton: Option<(EverythingWithSingletonTailArgs, Bytes32)>,
+}
+
+pub async fn cli_scan_asset(
+    asset_id_str: String,
+    coin_ids_str: Option<String>,
+    launcher_id_str: Option<String>,
+    max_depth: u32,
+    network: &Network,
+) -> Result<(), RcliError> {
+    let asset_id = hex_string_to_bytes32(&asset_id_str)?;
+    record_cat(asset_id, None);
+
+    let known_vaults = get_known_vaults(launcher_id_str)?;
+
+    let mut ctx = SpendContext::new();
+    let client = get_rpc_client(network)?;
+
+    let coin_ids = match coin_ids_str {
+        Some(coin_ids_str) => coin_ids_str
+            .split(',')
+            .map(|coin_id| hex_string_to_bytes32(coin_id.trim()))
+            .collect::<Result<Vec<_>, _>>()?,
+        None => find_asset_coin_ids(&client, &mut ctx, asset_id, &known_vaults).await?,
+    };
+
+    // The given coins and their ancestors (up to max_depth per coin); the TAIL
+    //  is revealed when the eve coins are spent
+    progress!("Sampling coins of asset 0x{}...", hex::encode(asset_id));
+    let mut visited = HashSet::new();
+    let mut sampled: Vec<(Bytes32, Option<Bytes32>)> = Vec::new();
+    let mut revealed_tail: Option<RevealedTail> = None;
+    let mut revoked_coins: Vec<(Bytes32, Option<P2DelegatedBySingletonLayer>)> = Vec::new();
+
+    for coin_id in coin_ids {
+        let Some(coin_record) = client.get_coin_record_by_name(coin_id).await?.coin_record else {
+            progress!("  Coin 0x{} not found; skipping", hex::encode(coin_id));
+            continue;
+        };
+        let mut spend_info = if coin_record.spent {
+            get_coin_spend(&client, coin_id, coin_record.spent_block_index)
+                .await?
+                .map(|coin_spend| parse_cat_spend(&mut ctx, &coin_spend))
+                .transpose()?
+                .flatten()
+        } else {
+            None
+        };
+
+        // Eve coins can't be parsed from their parent (which is not a CAT),
+        //  but their own spend shows what they are
+        let (coin_asset_id, mut hidden_puzzle_hash) =
+            match parse_cat_from_coin_record(&client, &mut ctx, &coin_record).await? {
+                Some(cat) => (cat.info.asset_id, cat.info.hidden_puzzle_hash),
+                None => match &spend_info {
+                    Some(spend_info) => (spend_info.asset_id, spend_info.hidden_puzzle_hash),
+                    None => {
+                        progress!(
+                            "  Coin 0x{} is not a CAT (or is an unspent eve coin); skipping",
+                            hex::encode(coin_id)
+                        );
+                        continue;
+                    }
+                },
+            };
+        if coin_asset_id != asset_id {
+            progress!(
+                "  Coin 0x{} is a CAT of another asset (0x{}); skipping",
+                hex::encode(coin_id),
+                hex::encode(coin_asset_id)
+            );
+            continue;
+        }
+
+        let mut coin = coin_record.coin;
+
+        for depth in 0..=max_depth {
+            let coin_id = coin.coin_id();
+            if !visited.insert(coin_id) {
+                break;
+            }
+            sampled.push((coin_id, hidden_puzzle_hash));
+
+            if let Some(spend_info) = spend_info.take() {
+                if let Some((tail, tail_solution)) = spend_info.tail
+                    && revealed_tail.is_none()
+                {
+                    revealed_tail = Some(parse_tail(&mut ctx, coin_id, tail, tail_solution)?);
+                }
+                if let Some(hidden_puzzle) = spend_info.hidden_puzzle {
+                    revoked_coins.push((
+                        coin_id,
+                        P2DelegatedBySingletonLayer::parse_puzzle(&ctx, hidden_puzzle)?,
+                    ));
+                }
+            }
+
+            if depth == max_depth {
+                break;
+            }
+
+            let parent_id = coin.parent_coin_info;
+            let Some(parent_record) = client.get_coin_record_by_name(parent_id).await?.coin_record
+            else {
+                break;
+            };
+            let Some(parent_spend) =
+                get_coin_spend(&client, parent_id, parent_record.spent_block_index).await?
+            else {
+                break;
+            };
+
+            // A parent that is not a CAT of the same asset means the coin is an eve coin
+            let Some(parent_info) = parse_cat_spend(&mut ctx, &parent_spend)? else {
+                break;
+            };
+            if parent_info.asset_id != asset_id {
+                break;
+            }
+
+            coin = parent_spend.coin;
+            hidden_puzzle_hash = parent_info.hidden_puzzle_hash;
+            spend_info = Some(parent_info);
+        }
+    }
+
+    if sampled.is_empty() {
+        return Err(RcliError::Custom(format!(
+            "None of the given coins is a coin of asset 0x{}",
+            hex::encode(asset_id)
+        )));
+    }
+    record_coin_ids(sampled.iter().map(|(coin_id, _)| *coin_id));
+
+    let mut hidden_puzzle_hashes: Vec<Bytes32> = Vec::new();
+    for (_, hidden_puzzle_hash) in &sampled {
+        if let Some(hidden_puzzle_hash) = hidden_puzzle_hash
+            && !hidden_puzzle_hashes.contains(hidden_puzzle_hash)
+        {
+            hidden_puzzle_hashes.push(*hidden_puzzle_hash);
+        }
+    }
+    let with_revocation_layer = sampled.iter().filter(|(_, hph)| hph.is_some()).count();
+    progress!(
+        "Sampled {} coins; {} of them have a revocation layer",
+        sampled.len(),
+        with_revocation_layer
+    );
+    if hidden_puzzle_hashes.len() == 1 {
+        record_cat(asset_id, hidden_puzzle_hashes.first().copied());
+    }
+
+    // Singletons the hidden puzzle could delegate to: (singleton struct hash, p2 nonce, source)
+    let mut candidates: Vec<(Bytes32, u64, String)> = Vec::new();
+    if let Some(RevealedTail {
+        coin_id,
+        everything_with_singleton: Some((tail_args, _)),
+        ..
+    }) = &revealed_tail
+    {
+        candidates.push((
+            tail_args.singleton_struct_hash,
+            0,
+            format!("TAIL revealed by coin 0x{}", hex::encode(coin_id)),
+        ));
+    }
+    for (coin_id, p2_layer) in &revoked_coins {
+        if let Some(p2_layer) = p2_layer {
+            candidates.push((
+                p2_layer.singleton_struct_hash,
+                p2_layer.nonce,
+                format!("hidden puzzle revealed by coin 0x{}", hex::encode(coin_id)),
+            ));
+        }
+    }
+    for (name, launcher_id, _) in &known_vaults {
+        candidates.push((get_singleton_struct_hash(*launcher_id), 0, name.clone()));
+    }
+
+    progress!("Revocation layer:");
+    let mut singleton_struct_hash: Option<Bytes32> = None;
+    if hidden_puzzle_hashes.is_empty() {
+        progress!(
+            "  No sampled coin has a hidden puzzle hash - the asset does not look revocable."
+        );
+    }
+    for hidden_puzzle_hash in &hidden_puzzle_hashes {
+        progress!(
+            "  Hidden puzzle hash 0x{} ({} coins)",
+            hex::encode(hidden_puzzle_hash),
+            sampled
+                .iter()
+                .filter(|(_, hph)| *hph == Some(*hidden_puzzle_hash))
+                .count()
+        );
+
+        let matching_candidate = candidates.iter().find(|(ssh, nonce, _)| {
+            Bytes32::from(P2DelegatedBySingletonLayerArgs::curry_tree_hash(
+                *ssh, *nonce,
+            )) == *hidden_puzzle_hash
+        });
+        match matching_candidate {
+            Some((ssh, nonce, source)) => {
+                progress!(
+                    "    Matches P2DelegatedBySingletonLayer (singleton struct hash 0x{}, nonce {}; from {})",
+                    hex::encode(ssh),
+                    nonce,
+                    source
+                );
+                singleton_struct_hash.get_or_insert(*ssh);
+            }
+            None => progress!(
+                "    Does not match P2DelegatedBySingletonLayer for any known singleton (the singleton may just be unknown)"
+            ),
+        }
+    }
+    for (coin_id, p2_layer) in &revoked_coins {
+        if p2_layer.is_none() {
+            progress!(
+                "  Coin 0x{} was spent through a hidden puzzle that is not a P2DelegatedBySingletonLayer",
+                hex::encode(coin_id)
+            );
+        }
+    }
+
+    progress!("TAIL:");
+    let mut tail_singleton_inner_puzzle_hash: Option<Bytes32> = None;
+    let mut nonce: Option<u64> = None;
+    match &revealed_tail {
+        Some(RevealedTail {
+            coin_id,
+            everything_with_singleton: Some((tail_args, singleton_inner_puzzle_hash)),
+            ..
+        }) => {
+            progress!(
+                "  EVERYTHING_WITH_SINGLETON_TAIL (revealed by coin 0x{}; singleton struct hash 0x{}, nonce {})",
+                hex::encode(coin_id),
+                hex::encode(tail_args.singleton_struct_hash),
+                tail_args.nonce
+            );
+            if singleton_struct_hash.is_some_and(|ssh| ssh != tail_args.singleton_struct_hash) {
+                progress!(
+                    "  Warning: the TAIL's singleton is not the one that controls the revocation layer"
+                );
+            }
+            singleton_struct_hash.get_or_insert(tail_args.singleton_struct_hash);
+            tail_singleton_inner_puzzle_hash = Some(*singleton_inner_puzzle_hash);
+            nonce = Some(tail_args.nonce);
+        }
+        Some(RevealedTail {
+            coin_id, mod_hash, ..
+        }) => progress!(
+            "  Not EVERYTHING_WITH_SINGLETON_TAIL (revealed by coin 0x{}; {})",
+            hex::encode(coin_id),
+            match mod_hash {
+                Some(mod_hash) => format!("curried mod hash 0x{}", hex::encode(mod_hash)),
+                None => "not a curried puzzle".to_string(),
+            }
+        ),
+        None => progress!(
+            "  Not revealed by the sampled coins (try other coins or a higher --max-depth)"
+        ),
+    }
+
+    let Some(singleton_struct_hash) = singleton_struct_hash else {
+        progress!("Could not recover the controlling launcher id.");
+        return Ok(());
+    };
+    record_singleton_struct_hash(singleton_struct_hash);
+
+    let mut launcher_id = known_vaults
+        .iter()
+        .find(|(_, launcher_id, _)| {
+            get_singleton_struct_hash(*launcher_id) == singleton_struct_hash
+        })
+        .map(|(_, launcher_id, _)| *launcher_id);
+    if launcher_id.is_none()
+        && let Some(singleton_inner_puzzle_hash) = tail_singleton_inner_puzzle_hash
+    {
+        progress!("Looking up the singleton that approved the issuance...");
+        launcher_id = find_singleton_launcher_id(
+            &client,
+            &mut ctx,
+            singleton_struct_hash,
+            singleton_inner_puzzle_hash,
+        )
+        .await?;
+    }
+    let Some(launcher_id) = launcher_id else {
+        progress!("Could not recover the controlling launcher id.");
+        return Ok(());
+    };
+    record_launcher_id(launcher_id);
+    progress!("Controlling launcher id: 0x{}", hex::encode(launcher_id));
+
+    // Without a TAIL reveal, the TAIL is still known if the asset id is one
+    //  of the vault's rCATs
+    if nonce.is_none() {
+        let profile_nonces = known_vaults
+            .iter()
+            .filter(|(_, known_launcher_id, _)| *known_launcher_id == launcher_id)
+            .flat_map(|(_, _, nonces)| nonces.iter().copied())
+            .collect::<Vec<_>>();
+        nonce = profile_nonces
+            .into_iter()
+            .find(|nonce| get_rcat_asset_id(launcher_id, *nonce) == asset_id);
+        if nonce.is_none() {
+            progress!("Looking up the vault's issuances...");
+            nonce = get_vault_nonces(&client, &mut ctx, launcher_id)
+                .await?
+                .into_iter()
+                .find(|nonce| get_rcat_asset_id(launcher_id, *nonce) == asset_id);
+        }
+
+        if let Some(nonce) = nonce {
+            progress!(
+                "  The asset id is the vault's EVERYTHING_WITH_SINGLETON_TAIL rCAT with nonce {}",
+                nonce
+            );
+        }
+    }
+
+    if let Some(nonce) = nonce {
+        record_rcat(launcher_id, nonce);
+    }
+
+    Ok(())
+}
+
+// Coins can't be looked up by asset id, so they're only found for rCATs issued
+//  by a known vault, by crawling the vault's issuances
+async fn find_asset_coin_ids(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    asset_id: Bytes32,
+    known_vaults: &[(String, Bytes32, Vec<u64>)],
+) -> Result<Vec<Bytes32>, RcliError> {
+    for (name, launcher_id, nonces) in known_vaults {
+        let mut nonce = nonces
+            .iter()
+            .copied()
+            .find(|nonce| get_rcat_asset_id(*launcher_id, *nonce) == asset_id);
+        // Profiles can belong to other networks
+        if nonce.is_none()
+            && client
+                .get_coin_record_by_name(*launcher_id)
+                .await?
+                .coin_record
+                .is_some()
+        {
+            nonce = get_vault_nonces(client, ctx, *launcher_id)
+                .await?
+                .into_iter()
+                .find(|nonce| get_rcat_asset_id(*launcher_id, *nonce) == asset_id);
+        }
+        let Some(nonce) = nonce else {
+            continue;
+        };
+
+        progress!(
+            "Asset was issued by the vault of {} (nonce {}); crawling its coins...",
+            name,
+            nonce
+        );
+        let holders = get_rcat_holders(client, ctx, *launcher_id, nonce).await?;
+        if holders.is_empty() {
+            return Err(RcliError::Custom(format!(
+                "Asset 0x{} has no unspent coins - use --coin-ids to sample spent ones",
+                hex::encode(asset_id)
+            )));
+        }
+
+        return Ok(holders
+            .iter()
+            .take(SCAN_ASSET_SAMPLE_SIZE)
+            .map(|cat| cat.coin.coin_id())
+            .collect());
+    }
+
+    Err(RcliError::Custom(format!(
+        "Asset 0x{} was not issued by a known vault, so its coins can't be found - use --coin-ids",
+        hex::encode(asset_id)
+    )))
+}
+
+async fn get_coin_spend(
+    client: &RpcClient,
+    coin_id: Bytes32,
+    spent_block_index: u32,
+) -> Result<Option<CoinSpend>, RcliError> {
+    Ok(client
+        .get_puzzle_and_solution(coin_id, Some(spent_block_index))
+        .await?
+        .coin_solution)
+}
+
+// Parses a CAT spend (with or without a revocation layer) and the conditions
+//  its inner puzzle creates
+fn parse_cat_spend(
+    ctx: &mut SpendContext,
+    coin_spend: &CoinSpend,
+) -> Result<Option<CatSpendInfo>, RcliError> {
+    let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+    let puzzle = Puzzle::parse(ctx, puzzle);
+    let solution = ctx.alloc(&coin_spend.solution)?;
+
+    let Some(cat_layer) = CatLayer::<Puzzle>::parse_puzzle(ctx, puzzle)? else {
+        return Ok(None);
+    };
+    let cat_solution = CatLayer::<Puzzle>::parse_solution(ctx, solution)?;
+
+    let (hidden_puzzle_hash, inner_puzzle, inner_solution, hidden_puzzle) =
+        match RevocationLayer::parse_puzzle(ctx, cat_layer.inner_puzzle)? {
+            Some(revocation_layer) => {
+                let RevocationSolution {
+                    puzzle: inner_puzzle,
+                    solution: inner_solution,
+                    hidden,
+                } = RevocationLayer::parse_solution(ctx, cat_solution.inner_puzzle_solution)?;
+
+                (
+                    Some(revocation_layer.hidden_puzzle_hash),
+                    inner_puzzle,
+                    inner_solution,
+                    hidden.then(|| Puzzle::parse(ctx, inner_puzzle)),
+                )
+            }
+            None => (
+                None,
+                cat_layer.inner_puzzle.ptr(),
+                cat_solution.inner_puzzle_solution,
+                None,
+            ),
+        };
+
+    let output = ctx.run(inner_puzzle, inner_solution)?;
+    let conditions = ctx.extract::<Vec<Condition<NodePtr>>>(output)?;
+    let tail = conditions
+        .into_iter()
+        .find_map(|condition| match condition {
+            Condition::RunCatTail(run_cat_tail) => {
+                Some((run_cat_tail.program, run_cat_tail.solution))
+            }
+            _ => None,
+        });
+
+    Ok(Some(CatSpendInfo {
+        asset_id: cat_layer.asset_id,
+        hidden_puzzle_hash,
+        tail,
+        hidden_puzzle,
+    }))
+}
+
+fn parse_tail(
+    ctx: &mut SpendContext,
+    coin_id: Bytes32,
+    tail: NodePtr,
+    tail_solution: NodePtr,
+) -> Result<RevealedTail, RcliError> {
+    let tail = Puzzle::parse(ctx, tail);
+    let Some(curried_tail) = tail.as_curried() else {
+        return Ok(RevealedTail {
+            coin_id,
+            mod_hash: None,
+            everything_with_singleton: None,
+        });
+    };
+
+    let everything_with_singleton = if curried_tail.mod_hash == EVERYTHING_WITH_SINGLETON_TAIL_HASH
+    {
+        let tail_args = ctx.extract::<EverythingWithSingletonTailArgs>(curried_tail.args)?;
+        let tail_solution = ctx.extract::<EverythingWithSingletonTailSolution>(tail_solution)?;
+        Some((tail_args, tail_solution.singleton_inner_puzzle_hash))
+    } else {
+        None
+    };
+
+    Ok(RevealedTail {
+        coin_id,
+        mod_hash: Some(curried_tail.mod_hash),
+        everything_with_singleton,
+    })
+}
+
+// The TAIL only receives a message from a coin with the singleton's full puzzle hash,
+//  so the singleton's coin can be found by puzzle hash; its puzzle reveal has the launcher id
+async fn find_singleton_launcher_id(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    singleton_struct_hash: Bytes32,
+    singleton_inner_puzzle_hash: Bytes32,
+) -> Result<Option<Bytes32>, RcliError> {
+    let singleton_puzzle_hash: Bytes32 = curry_tree_hash(
+        TreeHash::new(SINGLETON_TOP_LAYER_V1_1_HASH),
+        &[
+            TreeHash::new(singleton_struct_hash.to_bytes()),
+            TreeHash::new(singleton_inner_puzzle_hash.to_bytes()),
+        ],
+    )
+    .into();
+
+    let coin_records = client
+        .get_coin_records_by_puzzle_hashes(vec![singleton_puzzle_hash], None, None, Some(true))
+        .await?
+        .coin_records
+        .unwrap_or_default();
+    for coin_record in coin_records.into_iter().filter(|record| record.spent) {
+        let Some(coin_spend) = get_coin_spend(
+            client,
+            coin_record.coin.coin_id(),
+            coin_record.spent_block_index,
+        )
+        .await?
+        else {
+            continue;
+        };
+
+        let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+        let puzzle = Puzzle::parse(ctx, puzzle);
+        let Some(singleton_layer) = SingletonLayer::<Puzzle>::parse_puzzle(ctx, puzzle)? else {
+            continue;
+        };
+        if get_singleton_struct_hash(singleton_layer.launcher_id) == singleton_struct_hash {
+            return Ok(Some(singleton_layer.launcher_id));
+        }
+    }
+
+    Ok(None)
+}
@@ -1,34 +1,48 @@
-use chia::protocol::{Bytes32, SpendBundle};
-use chia_puzzle_types::singleton::SingletonStruct;
+use chia::{
+    bls::{PublicKey, Signature},
+    consensus::spendbundle_conditions::get_conditions_from_spendbundle,
+    protocol::{Bytes32, Coin, CoinSpend, SpendBundle},
+};
+use chia_puzzle_types::{cat::CatArgs, singleton::SingletonStruct};
 use chia_wallet_sdk::{
-    coinset::{ChiaRpcClient, CoinRecord, CoinsetClient},
+    coinset::{ChiaRpcClient, CoinRecord},
     driver::{
-        Asset, Cat, CatSpend, Layer, Offer, P2DelegatedBySingletonLayer, Puzzle, SingletonInfo,
-        SpendContext, StandardLayer, create_security_coin, decode_offer, spend_security_coin,
+        Asset, Cat, CatSpend, Layer, MedievalVault, P2DelegatedBySingletonLayer, Puzzle,
+        SingletonInfo, SpendContext, StandardLayer, create_security_coin, spend_security_coin,
     },
     prelude::ToTreeHash,
     types::{
-        Conditions, Mod,
-        puzzles::{P2DelegatedBySingletonLayerSolution, RevocationArgs},
+        Condition, Conditions, Mod,
+        puzzles::{
+            P2DelegatedBySingletonLayerArgs, P2DelegatedBySingletonLayerSolution, RevocationArgs,
+        },
     },
     utils::Address,
 };
 use clvm_traits::clvm_quote;
-use clvmr::NodePtr;
+use clvmr::{Allocator, NodePtr};
 use slot_machine::{
-    CliError, MultisigSingleton, SageClient, assets_xch_only, get_constants, get_prefix,
-    hex_string_to_pubkey, hex_string_to_signature, no_assets, sync_multisig_singleton,
-    wait_for_coin,
+    CliError, MultisigSingleton, hex_string_to_bytes32, sync_multisig_singleton, wait_for_coin,
 };
+use std::time::Duration;
 
-pub async fn get_first_address(wallet: &SageClient) -> Result<StandardLayer, CliError> {
-    let first_derivation_record = &wallet.get_derivations(false, 0, 1).await?.derivations[0];
-    let puzzle_hash_from_record = Address::decode(&first_derivation_record.address)?;
+use crate::{
+    EverythingWithSingletonTailArgs, Network, PendingVaultSpend, RcliError,
+    RevocationTransactionReport, RevokedCoinReport, RpcClient, VaultSpendOptions, WalletSigner,
+    add_melt_approval_conditions, add_rcat_tail_conditions, complete_vault_spend_with, progress,
+    record_coin_ids, select_vault_signers,
+};
 
-    let layer = StandardLayer::new(hex_string_to_pubkey(&first_derivation_record.public_key)?);
+pub async fn get_first_address(wallet: &WalletSigner) -> Result<StandardLayer, RcliError> {
+    let Some((public_key, puzzle_hash)) = wallet.get_derivations(1).await?.into_iter().next()
+    else {
+        return Err(RcliError::Custom("Wallet has no derivations".to_string()));
+    };
 
-    if puzzle_hash_from_record.puzzle_hash != layer.tree_hash().into() {
-        return Err(CliError::Custom(
+    let layer = StandardLayer::new(public_key);
+
+    if puzzle_hash != layer.tree_hash().into() {
+        return Err(RcliError::Custom(
             "Puzzle hash from record does not match standard layer hash".to_string(),
         ));
     }
@@ -36,183 +50,718 @@ pub async fn get_first_address(wallet: &SageClient) -> Result<StandardLayer, Cli
     Ok(layer)
 }
 
-#[allow(clippy::too_many_arguments)]
-pub async fn revoke_coins(
-    launcher_id: Bytes32,
-    testnet11: bool,
-    percentage: u8,
-    fee: u64,
-    asset_id: Bytes32,
-    hidden_puzzle_hash: Bytes32,
-    client: &CoinsetClient,
-    coin_records: Vec<CoinRecord>,
-) -> Result<(), CliError> {
-    println!("Revoking {} coins...", coin_records.len());
+// Vault members controlled by the wallet are found by looking at
+//  this many (unhardened) derivations
+pub const WALLET_DERIVATION_SCAN_LIMIT: u32 = 1000;
 
-    let mut ctx = SpendContext::new();
+// Returns the public keys of the first `limit` derivations of the wallet
+pub async fn get_wallet_public_keys(
+    wallet: &WalletSigner,
+    limit: u32,
+) -> Result<Vec<(PublicKey, Bytes32)>, RcliError> {
+    wallet.get_derivations(limit).await
+}
+
+// Syncs the vault to its latest coin
+pub async fn sync_vault(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+) -> Result<MedievalVault, RcliError> {
+    if client
+        .get_coin_record_by_name(launcher_id)
+        .await?
+        .coin_record
+        .is_none()
+    {
+        return Err(RcliError::VaultNotFound(launcher_id));
+    }
 
     let (MultisigSingleton::Vault(vault), _) =
-        sync_multisig_singleton::<()>(client, &mut ctx, launcher_id, None).await?
+        sync_multisig_singleton::<()>(client, ctx, launcher_id, None).await?
     else {
-        return Err(CliError::Custom("Could not sync vault".to_string()));
+        return Err(RcliError::VaultNotFound(launcher_id));
     };
 
-    println!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+    Ok(vault)
+}
 
-    let mut total_cat_amount = 0;
-    let mut total_revoked_amount = 0;
-    let mut amount_to_revoke: Vec<u64> = Vec::with_capacity(coin_records.len());
-    let mut cats: Vec<Cat> = Vec::with_capacity(coin_records.len());
-    for coin_record in coin_records {
-        if coin_record.spent {
-            return Err(CliError::Custom(format!(
-                "Coin {} already spent",
-                hex::encode(coin_record.coin.coin_id())
+// How long to wait for a submitted transaction to be included in a block
+pub const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+// Pushes the bundle to the mempool; returns the status reported by the node
+pub async fn push_spend_bundle(client: &RpcClient, sb: SpendBundle) -> Result<String, RcliError> {
+    let resp = client.push_tx(sb).await?;
+    if !resp.success {
+        return Err(RcliError::PushRejected(resp.error.unwrap_or(resp.status)));
+    }
+
+    Ok(resp.status)
+}
+
+// Waits until the given coin is spent, giving up after CONFIRMATION_TIMEOUT
+pub async fn wait_for_confirmation(client: &RpcClient, coin_id: Bytes32) -> Result<(), RcliError> {
+    tokio::time::timeout(CONFIRMATION_TIMEOUT, wait_for_coin(client, coin_id, true))
+        .await
+        .map_err(|_| RcliError::ConfirmationTimeout(coin_id))??;
+
+    Ok(())
+}
+
+// Returns the vault members (in vault order) the wallet can sign for
+//  Errors if the wallet does not control at least m of them
+pub async fn get_vault_signing_keys(
+    wallet: &WalletSigner,
+    vault: &MedievalVault,
+) -> Result<Vec<PublicKey>, RcliError> {
+    let wallet_keys = get_wallet_public_keys(wallet, WALLET_DERIVATION_SCAN_LIMIT).await?;
+
+    let signing_keys = vault
+        .info
+        .public_key_list
+        .iter()
+        .filter(|member| wallet_keys.iter().any(|(key, _)| key == *member))
+        .take(vault.info.m)
+        .copied()
+        .collect::<Vec<PublicKey>>();
+
+    if signing_keys.len() < vault.info.m {
+        return Err(RcliError::Custom(format!(
+            "Connected wallet controls {} of the {} keys required to spend the vault",
+            signing_keys.len(),
+            vault.info.m
+        )));
+    }
+
+    Ok(signing_keys)
+}
+
+pub fn get_rcat_asset_id(launcher_id: Bytes32, nonce: u64) -> Bytes32 {
+    EverythingWithSingletonTailArgs::new(launcher_id, nonce)
+        .curry_tree_hash()
+        .into()
+}
+
+pub fn get_singleton_struct_hash(launcher_id: Bytes32) -> Bytes32 {
+    SingletonStruct::new(launcher_id).tree_hash().into()
+}
+
+// All rCATs issued by a vault share the same hidden puzzle (the vault's p2 puzzle)
+pub fn get_rcat_hidden_puzzle_hash(launcher_id: Bytes32) -> Bytes32 {
+    P2DelegatedBySingletonLayerArgs::curry_tree_hash(get_singleton_struct_hash(launcher_id), 0)
+        .into()
+}
+
+// Full puzzle hash of an rCAT owned by the given inner puzzle
+pub fn get_rcat_puzzle_hash(
+    launcher_id: Bytes32,
+    nonce: u64,
+    inner_puzzle_hash: Bytes32,
+) -> Bytes32 {
+    CatArgs::curry_tree_hash(
+        get_rcat_asset_id(launcher_id, nonce),
+        RevocationArgs::new(get_rcat_hidden_puzzle_hash(launcher_id), inner_puzzle_hash)
+            .curry_tree_hash(),
+    )
+    .into()
+}
+
+// Parses a destination given as an address, a puzzle hash, or 'vault'
+//  (the vault's p2 puzzle, which is also the rCATs' hidden puzzle)
+pub fn parse_destination(
+    destination: &str,
+    launcher_id: Bytes32,
+    network: &Network,
+) -> Result<Bytes32, RcliError> {
+    if destination == "vault" {
+        return Ok(get_rcat_hidden_puzzle_hash(launcher_id));
+    }
+
+    if let Ok(address) = Address::decode(destination) {
+        if address.prefix != network.prefix() {
+            return Err(RcliError::Custom(format!(
+                "Destination {} is not a {} address",
+                destination,
+                network.prefix()
             )));
         }
-        println!(
-            "Parsing parent spend for coin 0x{}...",
-            hex::encode(coin_record.coin.coin_id())
-        );
-        let Some(parent_spend) = client
-            .get_puzzle_and_solution(
-                coin_record.coin.parent_coin_info,
-                Some(coin_record.confirmed_block_index),
-            )
+
+        return Ok(address.puzzle_hash);
+    }
+
+    hex_string_to_bytes32(&destination.replace("0x", ""))
+}
+
+// Returns the coin record and spend of every vault coin that has been spent
+//  (launcher spend excluded), oldest first
+pub async fn get_vault_spends(
+    client: &RpcClient,
+    launcher_id: Bytes32,
+) -> Result<Vec<(CoinRecord, CoinSpend)>, RcliError> {
+    let mut spends = Vec::new();
+    let mut coin_id = launcher_id;
+
+    loop {
+        let Some(coin_record) = client.get_coin_record_by_name(coin_id).await?.coin_record else {
+            return Err(RcliError::Custom(format!(
+                "Could not find coin 0x{}",
+                hex::encode(coin_id)
+            )));
+        };
+        if !coin_record.spent {
+            break;
+        }
+
+        if coin_id != launcher_id {
+            let Some(coin_spend) = client
+                .get_puzzle_and_solution(coin_id, Some(coin_record.spent_block_index))
+                .await?
+                .coin_solution
+            else {
+                return Err(CliError::CoinNotSpent(coin_id).into());
+            };
+
+            spends.push((coin_record, coin_spend));
+        }
+
+        let Some(children) = client
+            .get_coin_records_by_parent_ids(vec![coin_id], None, None, Some(true))
             .await?
-            .coin_solution
+            .coin_records
         else {
-            return Err(CliError::CoinNotSpent(coin_record.coin.parent_coin_info));
+            break;
         };
 
-        let parent_puzzle = ctx.alloc(&parent_spend.puzzle_reveal)?;
-        let parent_puzzle = Puzzle::parse(&ctx, parent_puzzle);
-        let parent_solution = ctx.alloc(&parent_spend.solution)?;
-        let Some(children) =
-            Cat::parse_children(&mut ctx, parent_spend.coin, parent_puzzle, parent_solution)?
-        else {
-            return Err(CliError::Custom(
-                "Failed to parse parent CAT spend".to_string(),
-            ));
+        // Singletons always have an odd amount
+        let Some(child) = children.into_iter().find(|c| c.coin.amount % 2 == 1) else {
+            break;
+        };
+        coin_id = child.coin.coin_id();
+    }
+
+    Ok(spends)
+}
+
+// Runs the spend and returns the conditions it outputs
+pub fn get_spend_conditions(
+    ctx: &mut SpendContext,
+    coin_spend: &CoinSpend,
+) -> Result<Vec<Condition<NodePtr>>, RcliError> {
+    let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+    let solution = ctx.alloc(&coin_spend.solution)?;
+    let output = ctx.run(puzzle, solution)?;
+
+    Ok(ctx.extract::<Vec<Condition<NodePtr>>>(output)?)
+}
+
+// Returns (message, receiver coin id) for each coin-coin message (mode 23)
+//  sent by the given spend
+pub fn get_sent_messages(
+    ctx: &mut SpendContext,
+    coin_spend: &CoinSpend,
+) -> Result<Vec<(Vec<u8>, Bytes32)>, RcliError> {
+    let conditions = get_spend_conditions(ctx, coin_spend)?;
+
+    let mut messages = Vec::new();
+    for condition in conditions {
+        let Condition::SendMessage(send_message) = condition else {
+            continue;
         };
+        if send_message.mode != 23 || send_message.data.len() != 1 {
+            continue;
+        }
+
+        let receiver_coin_id = ctx.extract::<Bytes32>(send_message.data[0])?;
+        messages.push((send_message.message.to_vec(), receiver_coin_id));
+    }
+
+    Ok(messages)
+}
 
-        let cat_coin_id = coin_record.coin.coin_id();
-        let cat = children
-            .into_iter()
-            .find(|c| c.coin.coin_id() == cat_coin_id)
-            .unwrap();
+// Fetches the coin's parent spend and parses the coin as a CAT
+pub async fn get_cat_from_coin_record(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    coin_record: &CoinRecord,
+) -> Result<Cat, RcliError> {
+    let Some(cat) = parse_cat_from_coin_record(client, ctx, coin_record).await? else {
+        return Err(RcliError::Custom(
+            "Failed to parse parent CAT spend".to_string(),
+        ));
+    };
+
+    Ok(cat)
+}
+
+// Like `get_cat_from_coin_record`, but returns None if the coin is not a CAT
+pub async fn parse_cat_from_coin_record(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    coin_record: &CoinRecord,
+) -> Result<Option<Cat>, RcliError> {
+    let Some(parent_spend) = client
+        .get_puzzle_and_solution(
+            coin_record.coin.parent_coin_info,
+            Some(coin_record.confirmed_block_index),
+        )
+        .await?
+        .coin_solution
+    else {
+        return Err(CliError::CoinNotSpent(coin_record.coin.parent_coin_info).into());
+    };
+
+    let parent_puzzle = ctx.alloc(&parent_spend.puzzle_reveal)?;
+    let parent_puzzle = Puzzle::parse(ctx, parent_puzzle);
+    let parent_solution = ctx.alloc(&parent_spend.solution)?;
+    let Some(children) =
+        Cat::parse_children(ctx, parent_spend.coin, parent_puzzle, parent_solution)?
+    else {
+        return Ok(None);
+    };
+
+    let cat_coin_id = coin_record.coin.coin_id();
+    Ok(children
+        .into_iter()
+        .find(|c| c.coin.coin_id() == cat_coin_id))
+}
+
+// Parses unspent coins as rCATs with the given asset id and hidden puzzle hash
+pub async fn parse_rcat_coins(
+    client: &RpcClient,
+    ctx: &mut SpendContext,
+    coin_records: Vec<CoinRecord>,
+    asset_id: Bytes32,
+    hidden_puzzle_hash: Bytes32,
+) -> Result<Vec<Cat>, RcliError> {
+    let mut cats: Vec<Cat> = Vec::with_capacity(coin_records.len());
+    for coin_record in coin_records {
+        if coin_record.spent {
+            return Err(RcliError::CoinAlreadySpent(coin_record.coin.coin_id()));
+        }
+        progress!(
+            "Parsing parent spend for coin 0x{}...",
+            hex::encode(coin_record.coin.coin_id())
+        );
+        let cat = get_cat_from_coin_record(client, ctx, &coin_record).await?;
 
         if cat.info.asset_id != asset_id || cat.info.hidden_puzzle_hash != Some(hidden_puzzle_hash)
         {
-            return Err(CliError::Custom(format!(
-                "Coin {} is a CAT but has the wrong asset id/puzzle hash",
-                hex::encode(cat_coin_id)
-            )));
+            return Err(RcliError::WrongAssetId(cat.coin.coin_id()));
         }
 
         cats.push(cat);
-        total_cat_amount += cat.coin.amount;
+    }
+
+    Ok(cats)
+}
+
+// Spends rCATs through their hidden puzzle, which delegates to the vault
+// Each rCAT outputs the conditions it's paired with; the returned conditions
+//  must be output by the vault to approve the spends
+pub fn spend_rcats_via_vault(
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    singleton_inner_puzzle_hash: Bytes32,
+    cats_and_conditions: Vec<(Cat, Conditions)>,
+) -> Result<Conditions, RcliError> {
+    let singleton_struct_hash: Bytes32 = SingletonStruct::new(launcher_id).tree_hash().into();
+    let hidden_puzzle_layer = P2DelegatedBySingletonLayer::new(singleton_struct_hash, 0);
 
-        let amount_to_keep = cat.coin.amount * percentage as u64 / 100;
-        let to_revoke = cat.coin.amount - amount_to_keep;
+    let mut cat_spends: Vec<CatSpend> = Vec::with_capacity(cats_and_conditions.len());
+    let mut vault_conditions = Conditions::new();
+    for (cat, conditions) in cats_and_conditions {
+        let delegated_puzzle = ctx.alloc(&clvm_quote!(conditions))?;
+
+        let delegated_puzzle_hash: Bytes32 = ctx.tree_hash(delegated_puzzle).into();
+        vault_conditions = vault_conditions.send_message(
+            23,
+            delegated_puzzle_hash.into(),
+            vec![ctx.alloc(&cat.coin.coin_id())?],
+        );
 
-        amount_to_revoke.push(to_revoke);
-        total_revoked_amount += to_revoke;
+        let inner_spend = hidden_puzzle_layer.construct_spend(
+            ctx,
+            P2DelegatedBySingletonLayerSolution {
+                singleton_inner_puzzle_hash,
+                delegated_puzzle,
+                delegated_solution: NodePtr::NIL,
+            },
+        )?;
+        cat_spends.push(CatSpend::revoke(cat, inner_spend));
     }
 
-    println!(
+    let _ = Cat::spend_all(ctx, &cat_spends)?;
+
+    Ok(vault_conditions)
+}
+
+// Revokes the given rCAT coins; the revoked amount is either melted (if `burn`
+//  is set) or sent to the destination (defaults to the wallet's first address)
+#[allow(clippy::too_many_arguments)]
+pub async fn revoke_coins(
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    percentage: u8,
+    fee: u64,
+    burn: bool,
+    destination: Option<Bytes32>,
+    spend_options: &VaultSpendOptions,
+    client: &RpcClient,
+    coin_records: Vec<CoinRecord>,
+) -> Result<Option<RevocationTransactionReport>, RcliError> {
+    let asset_id = get_rcat_asset_id(launcher_id, nonce);
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
+
+    let mut ctx = SpendContext::new();
+    let cats =
+        parse_rcat_coins(client, &mut ctx, coin_records, asset_id, hidden_puzzle_hash).await?;
+
+    revoke_cats(
+        launcher_id,
+        nonce,
+        network,
+        percentage,
+        fee,
+        burn,
+        destination,
+        spend_options,
+        client,
+        cats,
+        |_| Ok(()),
+    )
+    .await
+}
+
+// Same as `revoke_coins`, but for already-parsed rCATs
+// The vault is synced right before the spend is built; `on_submit` is called
+//  with the transaction's report right before the signed bundle is pushed
+// Returns a report of the confirmed transaction (None if it was not submitted)
+#[allow(clippy::too_many_arguments)]
+pub async fn revoke_cats<F>(
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    percentage: u8,
+    fee: u64,
+    burn: bool,
+    destination: Option<Bytes32>,
+    spend_options: &VaultSpendOptions,
+    client: &RpcClient,
+    cats: Vec<Cat>,
+    on_submit: F,
+) -> Result<Option<RevocationTransactionReport>, RcliError>
+where
+    F: FnOnce(&RevocationTransactionReport) -> Result<(), RcliError>,
+{
+    if cats.is_empty() {
+        return Err(RcliError::Custom("No coins to revoke".to_string()));
+    }
+    progress!("Revoking {} coins...", cats.len());
+    record_coin_ids(cats.iter().map(|cat| cat.coin.coin_id()));
+
+    let mut ctx = SpendContext::new();
+
+    let vault = sync_vault(client, &mut ctx, launcher_id).await?;
+
+    progress!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+
+    let total_cat_amount = cats.iter().map(|cat| cat.coin.amount).sum::<u64>();
+    let total_revoked_amount = cats
+        .iter()
+        .map(|cat| get_revoked_amount(cat.coin.amount, percentage))
+        .sum::<u64>();
+
+    progress!(
         "Revoking {} rCATs (total amount {:.3}; total revoked amount {:.3})...",
         cats.len(),
         total_cat_amount as f64 / 1000.0,
         total_revoked_amount as f64 / 1000.0
     );
+    if burn && total_revoked_amount == 0 {
+        return Err(RcliError::Custom("Nothing to burn".to_string()));
+    }
 
-    let wallet = SageClient::new()?;
-    let offer_resp = wallet
-        .make_offer(no_assets(), assets_xch_only(1), fee, None, None, false)
-        .await?;
-    println!("Offer with id {} created.", offer_resp.offer_id);
+    let wallet = WalletSigner::new(network)?;
+    let signers = select_vault_signers(&wallet, &vault, spend_options).await?;
+    let vault_coin_id = vault.coin.coin_id();
+    let funding = wallet.fund(&mut ctx, 1, fee, false).await?;
+    progress!("{}", funding);
 
     // Create security coin
-    let offer = Offer::from_spend_bundle(&mut ctx, &decode_offer(&offer_resp.offer)?)?;
-    let (security_sk, security_coin) =
-        create_security_coin(&mut ctx, offer.offered_coins().xch[0])?;
+    let (security_sk, security_coin) = create_security_coin(&mut ctx, funding.coin)?;
 
     // Spend security coin, which will create the p2 singleton coin that
     //   does the messaging
-    let layer = get_first_address(&wallet).await?;
-    let user_ph: Bytes32 = layer.tree_hash().into();
-    println!(
-        "Revoked CATs will be sent to: {}",
-        Address::new(user_ph, get_prefix(testnet11)).encode()?
-    );
+    let target_ph: Bytes32 = if let Some(destination) = destination {
+        destination
+    } else {
+        get_first_address(&wallet).await?.tree_hash().into()
+    };
+    if burn {
+        progress!("Revoked CATs will be melted.");
+    } else {
+        progress!(
+            "Revoked CATs will be sent to: {}",
+            Address::new(target_ph, network.prefix()).encode()?
+        );
+    }
 
     let security_coin_sig = spend_security_coin(
         &mut ctx,
         security_coin,
         Conditions::new().assert_concurrent_spend(cats[0].coin.coin_id()),
         &security_sk,
-        get_constants(testnet11),
+        &network.constants(),
     )?;
 
-    // Spend rCATs
-    let singleton_struct_hash: Bytes32 = SingletonStruct::new(launcher_id).tree_hash().into();
-    let hidden_puzzle_layer = P2DelegatedBySingletonLayer::new(singleton_struct_hash, 0);
+    let coin_reports = cats
+        .iter()
+        .map(|cat| get_revoked_coin_report(launcher_id, nonce, cat, percentage))
+        .collect::<Vec<_>>();
+    spend_revocation(
+        &mut ctx,
+        launcher_id,
+        nonce,
+        network,
+        vault,
+        &signers,
+        cats,
+        percentage,
+        burn,
+        target_ph,
+    )?;
+
+    let offer_id = funding.offer_id();
+    // Assemble bundle - the vault spend still needs to be signed by its members
+    let sb = funding.take(SpendBundle::new(ctx.take(), security_coin_sig));
+
+    let mut transaction_report = None;
+    let result = complete_vault_spend_with(
+        client,
+        &wallet,
+        PendingVaultSpend {
+            launcher_id,
+            network: network.clone(),
+            signers,
+            vault_coin_id,
+            confirmation_coin_id: security_coin.coin_id(),
+            spend_bundle: sb,
+            signatures: vec![],
+        },
+        spend_options,
+        |sb| {
+            let report = RevocationTransactionReport {
+                transaction_id: sb.name(),
+                vault_coin_id,
+                fee,
+                destination: if burn { None } else { Some(target_ph) },
+                coins: coin_reports,
+            };
+            on_submit(&report)?;
+            transaction_report = Some(report);
+            Ok(())
+        },
+    )
+    .await;
+    if spend_options.dry_run {
+        wallet.cancel_funding(offer_id).await?;
+    }
+    result?;
+
+    Ok(transaction_report)
+}
+
+pub fn get_revoked_coin_report(
+    launcher_id: Bytes32,
+    nonce: u64,
+    cat: &Cat,
+    percentage: u8,
+) -> RevokedCoinReport {
+    let revoked_amount = get_revoked_amount(cat.coin.amount, percentage);
+
+    // Must match the refund coin created by `spend_revocation`
+    let refund_coin_id = if revoked_amount < cat.coin.amount {
+        let refund_puzzle_hash = CatArgs::curry_tree_hash(
+            get_rcat_asset_id(launcher_id, nonce),
+            RevocationArgs::new(
+                get_rcat_hidden_puzzle_hash(launcher_id),
+                cat.p2_puzzle_hash(),
+            )
+            .curry_tree_hash(),
+        );
+
+        Some(
+            Coin::new(
+                cat.coin.coin_id(),
+                refund_puzzle_hash.into(),
+                cat.coin.amount - revoked_amount,
+            )
+            .coin_id(),
+        )
+    } else {
+        None
+    };
+
+    RevokedCoinReport {
+        coin_id: cat.coin.coin_id(),
+        owner_puzzle_hash: cat.p2_puzzle_hash(),
+        original_amount: cat.coin.amount,
+        revoked_amount,
+        refund_coin_id,
+    }
+}
+
+// Returns the CLVM cost of the given spends (signatures are not checked)
+pub fn get_spends_cost(coin_spends: Vec<CoinSpend>, network: &Network) -> Result<u64, RcliError> {
+    let sb = SpendBundle::new(coin_spends, Signature::default());
+    let constants = &network.constants();
+    let conditions = get_conditions_from_spendbundle(
+        &mut Allocator::new(),
+        &sb,
+        constants.max_block_cost_clvm,
+        constants.hard_fork_height,
+        constants,
+    )
+    .map_err(|err| RcliError::Custom(format!("Could not measure spend cost: {:?}", err)))?;
+
+    Ok(conditions.cost)
+}
+
+// Splits the items into consecutive batches whose spends cost at most `max_cost`
+// Costs grow (almost) linearly with the number of items, so a batch that is too
+//  expensive is shrunk proportionally and measured again
+pub fn split_by_cost<T, C, D>(
+    mut items: Vec<T>,
+    max_cost: u64,
+    mut get_cost: C,
+    describe: D,
+) -> Result<Vec<Vec<T>>, RcliError>
+where
+    C: FnMut(&[T]) -> Result<u64, RcliError>,
+    D: Fn(&T) -> String,
+{
+    let mut batches = Vec::new();
+
+    while !items.is_empty() {
+        let mut batch_size = items.len();
+        loop {
+            let cost = get_cost(&items[..batch_size])?;
+            if cost <= max_cost {
+                break;
+            }
+            if batch_size == 1 {
+                return Err(RcliError::Custom(format!(
+                    "{} alone costs {}, which is over the limit of {}",
+                    describe(&items[0]),
+                    cost,
+                    max_cost
+                )));
+            }
+
+            // Aim 5% under the limit to avoid re-measuring too often
+            let new_batch_size =
+                (batch_size as u128 * max_cost as u128 * 95 / (cost as u128 * 100)) as usize;
+            batch_size = new_batch_size.clamp(1, batch_size - 1);
+        }
+
+        let rest = items.split_off(batch_size);
+        batches.push(items);
+        items = rest;
+    }
+
+    Ok(batches)
+}
+
+// Holders keep `percentage` of each coin (rounded down for them)
+pub fn get_revoked_amount(amount: u64, percentage: u8) -> u64 {
+    amount - amount * percentage as u64 / 100
+}
+
+// Spends the rCATs and the vault; the revoked amount is melted if `burn` is set,
+//  and sent to `target_ph` otherwise
+#[allow(clippy::too_many_arguments)]
+pub fn spend_revocation(
+    ctx: &mut SpendContext,
+    launcher_id: Bytes32,
+    nonce: u64,
+    network: &Network,
+    vault: MedievalVault,
+    signers: &[PublicKey],
+    cats: Vec<Cat>,
+    percentage: u8,
+    burn: bool,
+    target_ph: Bytes32,
+) -> Result<(), RcliError> {
+    if cats.is_empty() {
+        return Err(RcliError::Custom("No coins to revoke".to_string()));
+    }
+
+    let hidden_puzzle_hash = get_rcat_hidden_puzzle_hash(launcher_id);
     let singleton_inner_puzzle_hash: Bytes32 = vault.info.inner_puzzle_hash().into();
+    let first_cat_coin_id = cats[0].coin.coin_id();
+    let total_revoked_amount = cats
+        .iter()
+        .map(|cat| get_revoked_amount(cat.coin.amount, percentage))
+        .sum::<u64>();
 
-    let mut cat_spends: Vec<CatSpend> = Vec::with_capacity(cats.len());
-    let mut vault_conditions = Conditions::new();
+    // Spend rCATs
+    let mut cats_and_conditions = Vec::with_capacity(cats.len());
     for (i, cat) in cats.into_iter().enumerate() {
+        let amount_to_revoke = get_revoked_amount(cat.coin.amount, percentage);
         let owner_refund_ph = RevocationArgs::new(hidden_puzzle_hash, cat.p2_puzzle_hash())
             .curry_tree_hash()
             .into();
         let owner_refund_hint = ctx.hint(cat.p2_puzzle_hash())?;
 
-        let base_condition = if amount_to_revoke[i] == cat.coin.amount {
+        let base_condition = if amount_to_revoke == cat.coin.amount {
             Conditions::new()
         } else {
             Conditions::new().create_coin(
                 owner_refund_ph,
-                cat.coin.amount - amount_to_revoke[i],
+                cat.coin.amount - amount_to_revoke,
                 owner_refund_hint,
             )
         };
 
-        let delegated_puzzle = if i == 0 {
-            let target_puzzle_hash = RevocationArgs::new(hidden_puzzle_hash, user_ph)
+        let conditions = if i == 0 && burn {
+            // The first rCAT runs the TAIL, melting the revoked amount
+            add_rcat_tail_conditions(
+                ctx,
+                launcher_id,
+                nonce,
+                singleton_inner_puzzle_hash,
+                base_condition,
+            )?
+        } else if i == 0 {
+            let target_puzzle_hash = RevocationArgs::new(hidden_puzzle_hash, target_ph)
                 .curry_tree_hash()
                 .into();
-            let user_hint = ctx.hint(user_ph)?;
+            let target_hint = ctx.hint(target_ph)?;
 
-            ctx.alloc(&clvm_quote!(base_condition.create_coin(
-                target_puzzle_hash,
-                total_revoked_amount,
-                user_hint
-            )))?
+            base_condition.create_coin(target_puzzle_hash, total_revoked_amount, target_hint)
         } else {
-            ctx.alloc(&clvm_quote!(base_condition))?
+            base_condition
         };
 
-        let delegated_puzzle_hash: Bytes32 = ctx.tree_hash(delegated_puzzle).into();
-        vault_conditions = vault_conditions.send_message(
-            23,
-            delegated_puzzle_hash.into(),
-            vec![ctx.alloc(&cat.coin.coin_id())?],
-        );
+        cats_and_conditions.push((cat, conditions));
+    }
 
-        let inner_spend = hidden_puzzle_layer.construct_spend(
-            &mut ctx,
-            P2DelegatedBySingletonLayerSolution {
-                singleton_inner_puzzle_hash,
-                delegated_puzzle,
-                delegated_solution: NodePtr::NIL,
-            },
+    let mut vault_conditions = spend_rcats_via_vault(
+        ctx,
+        launcher_id,
+        singleton_inner_puzzle_hash,
+        cats_and_conditions,
+    )?;
+    if burn {
+        vault_conditions = add_melt_approval_conditions(
+            ctx,
+            first_cat_coin_id,
+            total_revoked_amount,
+            vault_conditions,
         )?;
-        cat_spends.push(CatSpend::revoke(cat, inner_spend));
     }
 
-    let _ = Cat::spend_all(&mut ctx, &cat_spends)?;
-
     // Spend vault
     let vault_hint = ctx.hint(launcher_id)?;
     vault_conditions = vault_conditions.create_coin(
@@ -220,34 +769,7 @@ pub async fn revoke_coins(
         vault.coin.amount,
         vault_hint,
     );
-    vault.spend(
-        &mut ctx,
-        &[layer.synthetic_key],
-        vault_conditions,
-        get_constants(testnet11).genesis_challenge,
-    )?;
-
-    // Sign vault spend using wallet
-    let spends = ctx.take();
-    let vault_spend = spends.last().unwrap().clone();
-    let vault_sig = hex_string_to_signature(
-        &wallet
-            .sign_coin_spends(vec![vault_spend], false, true)
-            .await?
-            .spend_bundle
-            .aggregated_signature,
-    )?;
-
-    // Assemble final bundle and submit
-    let sb = offer.take(SpendBundle::new(spends, security_coin_sig + &vault_sig));
-
-    println!("Submitting transaction...");
-    let resp = client.push_tx(sb).await?;
-
-    println!("Transaction submitted; status='{}'", resp.status);
-
-    wait_for_coin(client, security_coin.coin_id(), true).await?;
-    println!("Confirmed!");
+    vault.spend(ctx, signers, vault_conditions, network.genesis_challenge)?;
 
     Ok(())
 }
@@ -0,0 +1,205 @@
+use chia::{
+    bls::{PublicKey, Signature, aggregate},
+    protocol::{Bytes32, Coin, CoinSpend, SpendBundle},
+};
+use chia_wallet_sdk::{
+    driver::{Offer, SpendContext, decode_offer},
+    utils::Address,
+};
+use slot_machine::{
+    CliError, SageClient, assets_xch_only, hex_string_to_pubkey, hex_string_to_signature, no_assets,
+};
+use std::{env, fmt};
+
+use crate::{KeyfileSigner, Network, RcliError, progress};
+
+// If set, the keyfile at this path is used instead of the Sage wallet
+pub const KEYFILE_ENV_VAR: &str = "RCLI_KEYFILE";
+
+// Provides funds and signatures: either the Sage wallet (default) or an
+//  encrypted local keyfile, for environments where Sage can't run
+pub enum WalletSigner {
+    Sage(SageClient),
+    Keyfile(Box<KeyfileSigner>),
+}
+
+// An XCH coin locked by the settlement payments puzzle, which can be spent by
+//  anyone - it's used to create the security coin
+// The spends that created it still need to be added to the final bundle
+pub struct Funding {
+    pub coin: Coin,
+    source: FundingSource,
+}
+
+enum FundingSource {
+    Offer(Box<Offer>, String),
+    // Funding spends were added to the spend context; only their signature is missing
+    Signature(Signature, usize, u64),
+}
+
+impl Funding {
+    pub fn from_offer(offer: Offer, offer_id: String) -> Self {
+        Self {
+            coin: offer.offered_coins().xch[0],
+            source: FundingSource::Offer(Box::new(offer), offer_id),
+        }
+    }
+
+    pub fn from_signature(coin: Coin, signature: Signature, coin_count: usize, fee: u64) -> Self {
+        Self {
+            coin,
+            source: FundingSource::Signature(signature, coin_count, fee),
+        }
+    }
+
+    // Id of the Sage offer the funding came from, if any
+    pub fn offer_id(&self) -> Option<String> {
+        match &self.source {
+            FundingSource::Offer(_, offer_id) => Some(offer_id.clone()),
+            FundingSource::Signature(..) => None,
+        }
+    }
+
+    pub fn take(self, sb: SpendBundle) -> SpendBundle {
+        match self.source {
+            FundingSource::Offer(offer, _) => offer.take(sb),
+            FundingSource::Signature(signature, _, _) => SpendBundle::new(
+                sb.coin_spends,
+                aggregate(&[sb.aggregated_signature, signature]),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Funding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            FundingSource::Offer(_, offer_id) => write!(f, "Offer with id {} created.", offer_id),
+            FundingSource::Signature(_, coin_count, fee) => write!(
+                f,
+                "Funding with {} keyfile wallet coins (fee: {} mojos).",
+                coin_count, fee
+            ),
+        }
+    }
+}
+
+// Sage reports a wallet balance that's too low as an error message; it's turned
+//  into InsufficientCoins so the command exits with the matching code
+fn sage_funding_error<E: Into<CliError>>(err: E) -> RcliError {
+    let err: CliError = err.into();
+    let message = err.to_string();
+    if message.to_lowercase().contains("insufficient") {
+        return RcliError::InsufficientCoins(message);
+    }
+
+    RcliError::Cli(err)
+}
+
+impl WalletSigner {
+    pub fn new(network: &Network) -> Result<Self, RcliError> {
+        if let Ok(path) = env::var(KEYFILE_ENV_VAR) {
+            return Ok(Self::Keyfile(Box::new(KeyfileSigner::load(
+                &path,
+                network.clone(),
+            )?)));
+        }
+
+        Ok(Self::Sage(SageClient::new().map_err(|err| {
+            RcliError::WalletUnreachable(format!("could not set up Sage client: {err}"))
+        })?))
+    }
+
+    // Returns the synthetic public keys and puzzle hashes of the first `limit`
+    //  unhardened derivations
+    pub async fn get_derivations(
+        &self,
+        limit: u32,
+    ) -> Result<Vec<(PublicKey, Bytes32)>, RcliError> {
+        match self {
+            Self::Sage(wallet) => {
+                let mut keys = Vec::new();
+                let derivations = wallet
+                    .get_derivations(false, 0, limit)
+                    .await
+                    .map_err(|err| RcliError::WalletUnreachable(err.to_string()))?
+                    .derivations;
+                for derivation in derivations {
+                    let public_key = hex_string_to_pubkey(&derivation.public_key)?;
+                    let puzzle_hash = Address::decode(&derivation.address)?.puzzle_hash;
+
+                    keys.push((public_key, puzzle_hash));
+                }
+
+                Ok(keys)
+            }
+            // Keyfiles only use their first KEYFILE_DERIVATION_LIMIT derivations
+            Self::Keyfile(signer) => Ok(signer
+                .keys()
+                .iter()
+                .take(limit as usize)
+                .map(|(_, public_key, puzzle_hash)| (*public_key, *puzzle_hash))
+                .collect()),
+        }
+    }
+
+    // Locks `amount` mojos in a settlement payments coin and pays `fee`
+    // Nothing is printed; callers can display the returned funding
+    pub async fn fund(
+        &self,
+        ctx: &mut SpendContext,
+        amount: u64,
+        fee: u64,
+        auto_import: bool,
+    ) -> Result<Funding, RcliError> {
+        match self {
+            Self::Sage(wallet) => {
+                let offer_resp = wallet
+                    .make_offer(
+                        no_assets(),
+                        assets_xch_only(amount),
+                        fee,
+                        None,
+                        None,
+                        auto_import,
+                    )
+                    .await
+                    .map_err(sage_funding_error)?;
+                let offer = Offer::from_spend_bundle(ctx, &decode_offer(&offer_resp.offer)?)?;
+                Ok(Funding::from_offer(offer, offer_resp.offer_id))
+            }
+            Self::Keyfile(signer) => signer.fund(ctx, amount, fee).await,
+        }
+    }
+
+    // Dry runs never submit the funding, but Sage keeps the offered coins locked
+    //  until the offer is cancelled; keyfile funding needs no clean up
+    pub async fn cancel_funding(&self, offer_id: Option<String>) -> Result<(), RcliError> {
+        let (Self::Sage(wallet), Some(offer_id)) = (self, offer_id) else {
+            return Ok(());
+        };
+
+        wallet.cancel_offer(offer_id.clone(), 0, true).await?;
+        progress!("Offer with id {} cancelled.", offer_id);
+
+        Ok(())
+    }
+
+    // Signs for all public keys the wallet controls; other required signatures
+    //  are left out
+    pub async fn sign_coin_spends(
+        &self,
+        coin_spends: Vec<CoinSpend>,
+    ) -> Result<Signature, RcliError> {
+        match self {
+            Self::Sage(wallet) => hex_string_to_signature(
+                &wallet
+                    .sign_coin_spends(coin_spends, false, true)
+                    .await?
+                    .spend_bundle
+                    .aggregated_signature,
+            ),
+            Self::Keyfile(signer) => signer.sign_coin_spends(&coin_spends),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use chia::protocol::{Bytes, Bytes32, CoinSpend};
+use chia_wallet_sdk::{
+    driver::{SingletonInfo, SpendContext},
+    types::Condition,
+};
+use slot_machine::hex_string_to_bytes32;
+
+use crate::{
+    Network, RcliError, get_nonces_from_vault_spends, get_rcat_asset_id,
+    get_rcat_hidden_puzzle_hash, get_rpc_client, get_spend_conditions, get_vault_spends, progress,
+    record_coin_ids, record_launcher_id, sync_vault,
+};
+
+pub async fn cli_vault_info(launcher_id_str: String, network: &Network) -> Result<(), RcliError> {
+    let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+
+    let mut ctx = SpendContext::new();
+    let client = get_rpc_client(network)?;
+
+    let vault = sync_vault(&client, &mut ctx, launcher_id).await?;
+
+    progress!("Vault launcher id: {}", hex::encode(launcher_id));
+    record_launcher_id(launcher_id);
+    record_coin_ids([vault.coin.coin_id()]);
+    progress!("Latest vault coin: {}", hex::encode(vault.coin.coin_id()));
+    progress!("Amount: {} mojos", vault.coin.amount);
+    progress!(
+        "Inner puzzle hash: {}",
+        hex::encode(vault.info.inner_puzzle_hash())
+    );
+    progress!(
+        "Configuration: {}-of-{}",
+        vault.info.m,
+        vault.info.public_key_list.len()
+    );
+    for pubkey in &vault.info.public_key_list {
+        progress!("  {}", hex::encode(pubkey.to_bytes()));
+    }
+
+    progress!();
+    progress!(
+        "rCAT hidden puzzle hash: {}",
+        hex::encode(get_rcat_hidden_puzzle_hash(launcher_id))
+    );
+    let vault_spends = get_vault_spends(&client, launcher_id).await?;
+    let nonces =
+        get_nonces_from_vault_spends(&client, &mut ctx, launcher_id, &vault_spends).await?;
+    if nonces.is_empty() {
+        progress!("No rCATs issued yet.");
+    }
+    for nonce in nonces {
+        progress!(
+            "rCAT asset id (nonce {}): {}",
+            nonce,
+            hex::encode(get_rcat_asset_id(launcher_id, nonce))
+        );
+    }
+
+    progress!();
+    progress!("History:");
+    for (coin_record, coin_spend) in vault_spends {
+        progress!(
+            "  Height {}: spent coin 0x{}",
+            coin_record.spent_block_index,
+            hex::encode(coin_record.coin.coin_id())
+        );
+
+        for line in summarize_vault_spend(&mut ctx, &coin_spend)? {
+            progress!("    {}", line);
+        }
+    }
+
+    Ok(())
+}
+
+// Decodes the vault spend's conditions into human-readable lines
+pub fn summarize_vault_spend(
+    ctx: &mut SpendContext,
+    coin_spend: &CoinSpend,
+) -> Result<Vec<String>, RcliError> {
+    let mut summary = Vec::new();
+    let mut approved_spends = 0;
+
+    for condition in get_spend_conditions(ctx, coin_spend)? {
+        match condition {
+            Condition::SendMessage(send_message) => {
+                let receiver = send_message
+                    .data
+                    .first()
+                    .and_then(|ptr| ctx.extract::<Bytes32>(*ptr).ok())
+                    .map(|receiver| format!("0x{}", hex::encode(receiver)))
+                    .unwrap_or_else(|| "?".to_string());
+
+                if send_message.mode != 23 {
+                    summary.push(format!(
+                        "Message (mode {}) to {}: {}",
+                        send_message.mode,
+                        receiver,
+                        hex::encode(&send_message.message)
+                    ));
+                } else if send_message.message.is_empty() {
+                    summary.push(format!("Issuance approved for eve rCAT {}", receiver));
+                } else if send_message.message.len() == 32 {
+                    // Delegated puzzle hash - used for revocations
+                    approved_spends += 1;
+                } else {
+                    let delta = ctx.alloc(&Bytes::new(send_message.message.to_vec()))?;
+                    let delta = ctx.extract::<i64>(delta)?;
+
+                    if delta < 0 {
+                        summary.push(format!(
+                            "Melt of {:.3} rCATs approved for {}",
+                            -delta as f64 / 1000.0,
+                            receiver
+                        ));
+                    } else {
+                        summary.push(format!(
+                            "TAIL run with delta {} approved for {}",
+                            delta, receiver
+                        ));
+                    }
+                }
+            }
+            Condition::CreateCoin(create_coin) if create_coin.amount % 2 == 1 => {
+                if create_coin.puzzle_hash != coin_spend.coin.puzzle_hash {
+                    summary.push("Members/threshold changed (rotation)".to_string());
+                }
+            }
+            Condition::CreateCoin(create_coin) => {
+                summary.push(format!(
+                    "Created coin with puzzle hash {} ({} mojos)",
+                    hex::encode(create_coin.puzzle_hash),
+                    create_coin.amount
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    if approved_spends > 0 {
+        summary.insert(
+            0,
+            format!(
+                "Revocation / delegated spend approved for {} coins",
+                approved_spends
+            ),
+        );
+    }
+
+    Ok(summary)
+}
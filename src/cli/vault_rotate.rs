@@ -0,0 +1,114 @@
+use chia::protocol::{Bytes32, SpendBundle};
+use chia_puzzle_types::Memos;
+use chia_wallet_sdk::{
+    driver::{MedievalVaultHint, SpendContext, create_security_coin, spend_security_coin},
+    types::{Conditions, puzzles::P2MOfNDelegateDirectArgs},
+};
+use slot_machine::{hex_string_to_bytes32, parse_amount};
+
+use crate::{
+    Network, PendingVaultSpend, RcliError, VaultSpendOptions, WalletSigner, complete_vault_spend,
+    get_rpc_client, parse_vault_members, progress, record_launcher_id, select_vault_signers,
+    sync_vault,
+};
+
+#[allow(clippy::too_many_arguments)]
+pub async fn cli_vault_rotate(
+    launcher_id_str: String,
+    new_m: usize,
+    new_members: String,
+    fee_str: String,
+    export: Option<String>,
+    signers: Option<String>,
+    dry_run: bool,
+    network: &Network,
+) -> Result<(), RcliError> {
+    let launcher_id = hex_string_to_bytes32(&launcher_id_str)?;
+    record_launcher_id(launcher_id);
+    let fee = parse_amount(&fee_str, false)?;
+    let spend_options = VaultSpendOptions::parse(export, signers, dry_run)?;
+
+    let mut ctx = SpendContext::new();
+    let client = get_rpc_client(network)?;
+    let wallet = WalletSigner::new(network)?;
+
+    let new_pubkeys = parse_vault_members(&wallet, &new_members, network).await?;
+    if new_m == 0 || new_m > new_pubkeys.len() {
+        return Err(RcliError::Custom(format!(
+            "Invalid threshold: {}-of-{}",
+            new_m,
+            new_pubkeys.len()
+        )));
+    }
+
+    let vault = sync_vault(&client, &mut ctx, launcher_id).await?;
+
+    progress!("Latest vault coin: {:}", hex::encode(vault.coin.coin_id()));
+    progress!(
+        "Current configuration: {}-of-{}",
+        vault.info.m,
+        vault.info.public_key_list.len()
+    );
+    progress!("New configuration: {}-of-{}", new_m, new_pubkeys.len());
+    for pubkey in &new_pubkeys {
+        progress!("  {}", hex::encode(pubkey.to_bytes()));
+    }
+
+    let signers = select_vault_signers(&wallet, &vault, &spend_options).await?;
+    let vault_coin_id = vault.coin.coin_id();
+
+    let funding = wallet.fund(&mut ctx, 1, fee, false).await?;
+    progress!("{}", funding);
+
+    // Create security coin
+    let (security_sk, security_coin) = create_security_coin(&mut ctx, funding.coin)?;
+
+    let security_coin_sig = spend_security_coin(
+        &mut ctx,
+        security_coin,
+        Conditions::new().assert_concurrent_spend(vault_coin_id),
+        &security_sk,
+        &network.constants(),
+    )?;
+
+    // Spend vault into the new m-of-n inner puzzle - the launcher id stays the same
+    //  The hint allows the new members to be recovered when syncing
+    let new_inner_puzzle_hash: Bytes32 =
+        P2MOfNDelegateDirectArgs::curry_tree_hash(new_m, new_pubkeys.clone()).into();
+    let new_vault_hint = ctx.alloc(&MedievalVaultHint {
+        my_launcher_id: launcher_id,
+        m: new_m,
+        public_key_list: new_pubkeys,
+    })?;
+    let conditions = Conditions::new().create_coin(
+        new_inner_puzzle_hash,
+        vault.coin.amount,
+        Memos::Some(new_vault_hint),
+    );
+    vault.spend(&mut ctx, &signers, conditions, network.genesis_challenge)?;
+
+    let offer_id = funding.offer_id();
+    // Assemble bundle - the vault spend still needs to be signed by its members
+    let sb = funding.take(SpendBundle::new(ctx.take(), security_coin_sig));
+
+    let result = complete_vault_spend(
+        &client,
+        &wallet,
+        PendingVaultSpend {
+            launcher_id,
+            network: network.clone(),
+            signers,
+            vault_coin_id,
+            confirmation_coin_id: security_coin.coin_id(),
+            spend_bundle: sb,
+            signatures: vec![],
+        },
+        &spend_options,
+    )
+    .await;
+    if spend_options.dry_run {
+        wallet.cancel_funding(offer_id).await?;
+    }
+
+    result
+}
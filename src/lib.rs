@@ -1,5 +1,7 @@
+pub mod api;
 pub mod cli;
 pub mod types;
 
+pub use api::*;
 pub use cli::*;
 pub use types::*;
@@ -1,7 +1,10 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand, value_parser};
 use rcli::{
-    cli_generate_send_message_bundle, cli_issue, cli_launch_vault, cli_ping, cli_revoke,
-    cli_revoke_bulk,
+    Network, NetworkKind, OutputFormat, Profile, RcliError, cli_create_keyfile, cli_derive,
+    cli_generate_send_message_bundle, cli_holders, cli_inspect_coin, cli_issue, cli_launch_vault,
+    cli_melt, cli_nonces, cli_ping, cli_revoke, cli_revoke_bulk, cli_scan_asset,
+    cli_sign_vault_spend, cli_submit_vault_spend, cli_vault_info, cli_vault_rotate, load_profile,
+    print_command_output, set_output_format,
 };
 
 #[derive(Parser)]
@@ -13,6 +16,70 @@ use rcli::{
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Output format; with 'json', a single JSON object is printed to stdout and progress goes to stderr
+    #[arg(long, global = true, value_enum, default_value = "text")]
+    output: OutputFormat,
+
+    /// Profile from the config file (~/.config/rcli/config.toml) with default settings - e.g., launcher id, nonces and network
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    #[command(flatten)]
+    network: NetworkArgs,
+}
+
+#[derive(Args)]
+struct NetworkArgs {
+    /// Network to use (defaults to the profile's network, or mainnet); 'custom' requires --genesis-challenge, --address-prefix and a full node RPC, and uses testnet11's consensus rules unless --constants-base says otherwise
+    #[arg(long, global = true, value_enum)]
+    network: Option<NetworkKind>,
+
+    /// Shorthand for '--network testnet11'
+    #[arg(
+        long,
+        global = true,
+        default_value = "false",
+        conflicts_with = "network"
+    )]
+    testnet11: bool,
+
+    /// Genesis challenge of the custom network
+    #[arg(long, global = true)]
+    genesis_challenge: Option<String>,
+
+    /// AGG_SIG_ME additional data of the custom network (defaults to the genesis challenge)
+    #[arg(long, global = true)]
+    agg_sig_me_data: Option<String>,
+
+    /// Address prefix of the custom network (e.g., 'sim')
+    #[arg(long, global = true)]
+    address_prefix: Option<String>,
+
+    /// Network whose consensus constants (cost limits, fork heights) the custom network uses; defaults to testnet11
+    #[arg(long, global = true, value_enum)]
+    constants_base: Option<NetworkKind>,
+
+    /// Full node RPC URL to use instead of coinset.org (see RCLI_FULL_NODE_CERT/RCLI_FULL_NODE_KEY for its certificate)
+    #[arg(long, global = true)]
+    rpc_url: Option<String>,
+}
+
+impl NetworkArgs {
+    fn into_network(self, profile: &Profile) -> Result<Network, RcliError> {
+        profile.network(
+            if self.testnet11 {
+                Some(NetworkKind::Testnet11)
+            } else {
+                self.network
+            },
+            self.genesis_challenge,
+            self.agg_sig_me_data,
+            self.address_prefix,
+            self.rpc_url,
+            self.constants_base,
+        )
+    }
 }
 
 #[derive(Subcommand)]
@@ -20,71 +87,227 @@ enum Commands {
     /// Verifies Sage RPC connection and fetches the connected wallet's first address
     Ping {},
 
-    /// Launch a medieval vault (protected by a 1-of-1 of your first address)
+    /// Launch a medieval vault (m-of-n multisig; defaults to a 1-of-1 of your first address)
     LaunchVault {
-        /// Transaction fee
-        #[arg(long, default_value = "0.00042")]
-        fee: String,
+        /// Number of member signatures required to spend the vault
+        #[arg(long, default_value = "1")]
+        m: usize,
+
+        /// Comma-separated list of member public keys or addresses (addresses must belong to the connected wallet)
+        #[arg(long)]
+        members: Option<String>,
+
+        /// Transaction fee; defaults to the profile's fee, or 0.00042
+        #[arg(long)]
+        fee: Option<String>,
 
-        /// Use testnet11
+        /// Build and sign the transaction, validate it locally and exit without submitting
         #[arg(long, default_value = "false")]
-        testnet11: bool,
+        dry_run: bool,
+
+        /// Save the new vault's launcher id (and network) to this profile in the config file; prompted for otherwise
+        #[arg(long, conflicts_with = "dry_run")]
+        save_profile: Option<String>,
     },
 
     /// Issue the vault's rCAT
     Issue {
-        /// The vault launcher id
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// TAIL nonce (a vault can issue one rCAT per nonce); defaults to the profile's first nonce, or 0
         #[arg(long)]
-        launcher_id: String,
+        nonce: Option<u64>,
 
         /// The amount of rCATs to issue
         #[arg(long, default_value = "1337.420")]
         cat_amount: String,
 
-        /// Transaction fee
-        #[arg(long, default_value = "0.0")]
-        fee: String,
+        /// CSV file with recipients (address,amount[,memo]); overrides --cat-amount
+        #[arg(long)]
+        recipients: Option<String>,
 
-        /// Use testnet11
-        #[arg(long, default_value = "false")]
-        testnet11: bool,
+        /// Transaction fee (paid for each issuance transaction); defaults to the profile's fee, or 0.0
+        #[arg(long)]
+        fee: Option<String>,
+
+        /// Save the unsigned vault spend to this file instead of signing it with the connected wallet (for multi-party signing)
+        #[arg(long)]
+        export: Option<String>,
+
+        /// Comma-separated list of vault member public keys that will sign (defaults to the members controlled by the wallet, or the first m members when exporting)
+        #[arg(long)]
+        signers: Option<String>,
+
+        /// Build and sign the transaction, validate it locally and exit without submitting
+        #[arg(long, default_value = "false", conflicts_with = "export")]
+        dry_run: bool,
+    },
+
+    /// Compute the rCAT's asset id, hidden puzzle hash and puzzle hashes offline (without contacting Sage or an RPC)
+    Derive {
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// TAIL nonce (a vault can issue one rCAT per nonce); defaults to the profile's first nonce, or 0
+        #[arg(long)]
+        nonce: Option<u64>,
+
+        /// Comma-separated list of inner puzzle hashes or addresses to compute the full rCAT puzzle hash for
+        #[arg(long)]
+        inner: Option<String>,
+    },
+
+    /// Check whether a coin is an rCAT and whether a known vault (--launcher-id or a config profile) can revoke it
+    InspectCoin {
+        /// The coin id
+        coin_id: String,
+
+        /// Vault launcher id to check the coin against (in addition to the config profiles' vaults)
+        #[arg(long)]
+        launcher_id: Option<String>,
+    },
+
+    /// Check whether any CAT is revocable: parse the revocation layer and TAIL of sampled coins
+    ScanAsset {
+        /// The CAT asset id
+        asset_id: String,
+
+        /// Comma-separated ids of coins of the asset to sample (their ancestors are sampled too); coins can't be looked up by asset id, so this is required unless the asset was issued by a known vault
+        #[arg(long)]
+        coin_ids: Option<String>,
+
+        /// Vault launcher id to match the hidden puzzle against (in addition to the config profiles' vaults)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// How many ancestors of each given coin to follow while looking for the TAIL reveal
+        #[arg(long, default_value_t = 100)]
+        max_depth: u32,
+    },
+
+    /// List the TAIL nonces the vault has already issued rCATs with
+    Nonces {
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+    },
+
+    /// Find all unspent coins of the vault's rCAT and save them as a CSV usable with revoke-bulk
+    Holders {
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// TAIL nonce (a vault can issue one rCAT per nonce); defaults to the profile's first nonce, or 0
+        #[arg(long)]
+        nonce: Option<u64>,
+
+        /// Output CSV file
+        #[arg(long, default_value = "holders.csv")]
+        output_file: String,
     },
 
     /// Revoke the vault's rCAT
     Revoke {
-        /// The vault launcher id
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// TAIL nonce (a vault can issue one rCAT per nonce); defaults to the profile's first nonce, or 0
         #[arg(long)]
-        launcher_id: String,
+        nonce: Option<u64>,
 
         /// Percentage of original amount that rCAT holders get to keep (rounded down for them)
-        #[arg(long, default_value = "50")]
+        #[arg(long, default_value = "50", value_parser = value_parser!(u8).range(0..=100))]
         percentage: u8,
 
         /// Comma-separated list of rCAT coin ids to revoke
         #[arg(long)]
         coin_ids: String,
 
-        /// Transaction fee
-        #[arg(long, default_value = "0.0")]
-        fee: String,
+        /// Transaction fee; defaults to the profile's fee, or 0.0
+        #[arg(long)]
+        fee: Option<String>,
 
-        /// Use testnet11
+        /// Melt the revoked rCATs instead of sending them to the wallet
         #[arg(long, default_value = "false")]
-        testnet11: bool,
+        burn: bool,
+
+        /// Where revoked rCATs are sent: an address, a puzzle hash, or 'vault' (the vault's p2 puzzle); defaults to the profile's destination, or the wallet's first address
+        #[arg(long, conflicts_with = "burn")]
+        destination: Option<String>,
+
+        /// Save a report of the revoked coins to this file (.json or .csv)
+        #[arg(long, conflicts_with_all = ["export", "dry_run"])]
+        report: Option<String>,
+
+        /// Save the unsigned vault spend to this file instead of signing it with the connected wallet (for multi-party signing)
+        #[arg(long)]
+        export: Option<String>,
+
+        /// Comma-separated list of vault member public keys that will sign (defaults to the members controlled by the wallet, or the first m members when exporting)
+        #[arg(long)]
+        signers: Option<String>,
+
+        /// Build and sign the transaction, validate it locally and exit without submitting
+        #[arg(long, default_value = "false", conflicts_with = "export")]
+        dry_run: bool,
+    },
+
+    /// Melt (destroy) rCATs using the vault
+    Melt {
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// TAIL nonce (a vault can issue one rCAT per nonce); defaults to the profile's first nonce, or 0
+        #[arg(long)]
+        nonce: Option<u64>,
+
+        /// Comma-separated list of rCAT coin ids to melt from
+        #[arg(long)]
+        coin_ids: String,
+
+        /// Amount of rCATs to melt (defaults to the total amount of the given coins)
+        #[arg(long)]
+        amount: Option<String>,
+
+        /// Transaction fee; defaults to the profile's fee, or 0.0
+        #[arg(long)]
+        fee: Option<String>,
+
+        /// Save the unsigned vault spend to this file instead of signing it with the connected wallet (for multi-party signing)
+        #[arg(long)]
+        export: Option<String>,
+
+        /// Comma-separated list of vault member public keys that will sign (defaults to the members controlled by the wallet, or the first m members when exporting)
+        #[arg(long)]
+        signers: Option<String>,
+
+        /// Build and sign the transaction, validate it locally and exit without submitting
+        #[arg(long, default_value = "false", conflicts_with = "export")]
+        dry_run: bool,
     },
 
     /// Revoke a bulk of rCATs
     RevokeBulk {
-        /// The vault launcher id
+        /// The vault launcher id (defaults to the profile's)
         #[arg(long)]
-        launcher_id: String,
+        launcher_id: Option<String>,
 
-        /// CSV file containing holders
+        /// TAIL nonce (a vault can issue one rCAT per nonce); defaults to the profile's first nonce, or 0
         #[arg(long)]
-        csv: String,
+        nonce: Option<u64>,
+
+        /// CSV file containing holders
+        #[arg(long, required_unless_present = "resume")]
+        csv: Option<String>,
 
         /// Percentage of original amount that rCAT holders get to keep (rounded down for them)
-        #[arg(long, default_value = "50")]
+        #[arg(long, default_value = "50", value_parser = value_parser!(u8).range(0..=100))]
         percentage: u8,
 
         /// Minimum total number of coins to revoke
@@ -99,23 +322,79 @@ enum Commands {
         #[arg(long, default_value = "0.001")]
         min_coin_amount: String,
 
-        /// Comma-separated list of addresses to NOT revoke from
+        /// Comma-separated list of addresses to NOT revoke from (defaults to the profile's; required unless resuming)
+        #[arg(long)]
+        exclude_addresses: Option<String>,
+
+        /// Transaction fee; defaults to the profile's fee, or 0.0
         #[arg(long)]
-        exclude_addresses: String,
+        fee: Option<String>,
 
-        /// Transaction fee
-        #[arg(long, default_value = "0.0")]
-        fee: String,
+        /// Melt the revoked rCATs instead of sending them to the wallet
+        #[arg(long, default_value = "false")]
+        burn: bool,
+
+        /// Where revoked rCATs are sent: an address, a puzzle hash, or 'vault' (the vault's p2 puzzle); defaults to the profile's destination, or the wallet's first address
+        #[arg(long, conflicts_with = "burn")]
+        destination: Option<String>,
+
+        /// File where the run's progress is saved
+        #[arg(long, default_value = "revoke_bulk_state.json")]
+        state_file: String,
 
-        /// Use testnet11
+        /// Continue an interrupted run from the state file (the saved percentage, burn and destination settings are used)
         #[arg(long, default_value = "false")]
-        testnet11: bool,
+        resume: bool,
+
+        /// Save a report of the revoked coins to this file (.json or .csv)
+        #[arg(long, conflicts_with_all = ["export", "dry_run"])]
+        report: Option<String>,
+
+        /// Save the unsigned vault spend to this file instead of signing it with the connected wallet (for multi-party signing)
+        #[arg(long)]
+        export: Option<String>,
+
+        /// Comma-separated list of vault member public keys that will sign (defaults to the members controlled by the wallet, or the first m members when exporting)
+        #[arg(long)]
+        signers: Option<String>,
+
+        /// Build and sign the transaction, validate it locally and exit without submitting
+        #[arg(long, default_value = "false", conflicts_with = "export")]
+        dry_run: bool,
     },
+
+    /// Manage a medieval vault
+    Vault {
+        #[command(subcommand)]
+        command: VaultCommands,
+    },
+
+    /// Sign an exported vault spend using the connected wallet
+    SignVaultSpend {
+        /// File created using --export
+        #[arg(long)]
+        file: String,
+    },
+
+    /// Submit an exported vault spend once enough members signed it
+    SubmitVaultSpend {
+        /// File created using --export
+        #[arg(long)]
+        file: String,
+    },
+
+    /// Create an encrypted keyfile that can be used instead of Sage (set RCLI_KEYFILE to its path)
+    CreateKeyfile {
+        /// Keyfile path
+        #[arg(long)]
+        file: String,
+    },
+
     /// (UNSAFE - only use if you know what you're doing) Generate a partial signed bundle that has a vault p2 coin sending a puzzle-puzzle numeric message to a coin
     GenerateSendMessageBundle {
-        /// The vault launcher id
+        /// The vault launcher id (defaults to the profile's)
         #[arg(long)]
-        launcher_id: String,
+        launcher_id: Option<String>,
 
         /// Message
         #[arg(long)]
@@ -128,35 +407,207 @@ enum Commands {
         /// Spend bundle output file
         #[arg(long, default_value = "spend_bundle.json")]
         output_file: String,
+    },
+}
 
-        /// Use testnet11
-        #[arg(long, default_value = "false")]
-        testnet11: bool,
+#[derive(Subcommand)]
+enum VaultCommands {
+    /// Show the vault's current state, its rCATs and its spend history
+    Info {
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+    },
+
+    /// Change the vault's members and/or threshold (launcher id stays the same)
+    Rotate {
+        /// The vault launcher id (defaults to the profile's)
+        #[arg(long)]
+        launcher_id: Option<String>,
+
+        /// New number of member signatures required to spend the vault
+        #[arg(long)]
+        m: usize,
+
+        /// Comma-separated list of new member public keys or addresses (addresses must belong to the connected wallet)
+        #[arg(long)]
+        members: String,
+
+        /// Transaction fee; defaults to the profile's fee, or 0.0
+        #[arg(long)]
+        fee: Option<String>,
+
+        /// Save the unsigned vault spend to this file instead of signing it with the connected wallet (for multi-party signing)
+        #[arg(long)]
+        export: Option<String>,
+
+        /// Comma-separated list of vault member public keys that will sign (defaults to the members controlled by the wallet, or the first m members when exporting)
+        #[arg(long)]
+        signers: Option<String>,
+
+        /// Build and sign the transaction, validate it locally and exit without submitting
+        #[arg(long, default_value = "false", conflicts_with = "export")]
+        dry_run: bool,
     },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Cli::parse();
+    set_output_format(args.output);
+
+    let res = match load_profile(args.profile)
+        .and_then(|profile| Ok((args.network.into_network(&profile)?, profile)))
+    {
+        Ok((network, profile)) => run(args.command, &network, &profile).await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = &res {
+        eprintln!("Error: {err}");
+    }
+    let exit_code = res.as_ref().err().map(|err| err.exit_code());
+    print_command_output(res.err().map(|err| err.to_string()));
 
-    let res = match args.command {
+    if let Some(exit_code) = exit_code {
+        std::process::exit(exit_code);
+    }
+}
+
+async fn run(command: Commands, network: &Network, profile: &Profile) -> Result<(), RcliError> {
+    match command {
         Commands::Ping {} => cli_ping().await,
-        Commands::LaunchVault { fee, testnet11 } => cli_launch_vault(fee, testnet11).await,
+        Commands::LaunchVault {
+            m,
+            members,
+            fee,
+            dry_run,
+            save_profile,
+        } => {
+            cli_launch_vault(
+                m,
+                members,
+                profile.fee(fee, "0.00042"),
+                dry_run,
+                save_profile,
+                network,
+            )
+            .await
+        }
         Commands::Issue {
             launcher_id,
+            nonce,
             cat_amount,
+            recipients,
             fee,
-            testnet11,
-        } => cli_issue(launcher_id, cat_amount, fee, testnet11).await,
+            export,
+            signers,
+            dry_run,
+        } => {
+            cli_issue(
+                profile.launcher_id(launcher_id)?,
+                profile.nonce(nonce)?,
+                cat_amount,
+                recipients,
+                profile.fee(fee, "0.0"),
+                export,
+                signers,
+                dry_run,
+                network,
+            )
+            .await
+        }
+        Commands::Derive {
+            launcher_id,
+            nonce,
+            inner,
+        } => cli_derive(
+            profile.launcher_id(launcher_id)?,
+            profile.nonce(nonce)?,
+            inner,
+            network,
+        ),
+        Commands::InspectCoin {
+            coin_id,
+            launcher_id,
+        } => cli_inspect_coin(coin_id, launcher_id, network).await,
+        Commands::ScanAsset {
+            asset_id,
+            coin_ids,
+            launcher_id,
+            max_depth,
+        } => cli_scan_asset(asset_id, coin_ids, launcher_id, max_depth, network).await,
+        Commands::Nonces { launcher_id } => {
+            cli_nonces(profile.launcher_id(launcher_id)?, network).await
+        }
+        Commands::Holders {
+            launcher_id,
+            nonce,
+            output_file,
+        } => {
+            cli_holders(
+                profile.launcher_id(launcher_id)?,
+                profile.nonce(nonce)?,
+                output_file,
+                network,
+            )
+            .await
+        }
         Commands::Revoke {
             launcher_id,
+            nonce,
             percentage,
             coin_ids,
             fee,
-            testnet11,
-        } => cli_revoke(launcher_id, percentage, coin_ids, fee, testnet11).await,
+            burn,
+            destination,
+            report,
+            export,
+            signers,
+            dry_run,
+        } => {
+            cli_revoke(
+                profile.launcher_id(launcher_id)?,
+                profile.nonce(nonce)?,
+                percentage,
+                coin_ids,
+                profile.fee(fee, "0.0"),
+                burn,
+                profile.destination(destination, burn),
+                report,
+                export,
+                signers,
+                dry_run,
+                network,
+            )
+            .await
+        }
+        Commands::Melt {
+            launcher_id,
+            nonce,
+            coin_ids,
+            amount,
+            fee,
+            export,
+            signers,
+            dry_run,
+        } => {
+            cli_melt(
+                profile.launcher_id(launcher_id)?,
+                profile.nonce(nonce)?,
+                coin_ids,
+                amount,
+                profile.fee(fee, "0.0"),
+                export,
+                signers,
+                dry_run,
+                network,
+            )
+            .await
+        }
         Commands::RevokeBulk {
             launcher_id,
+            nonce,
             csv,
             percentage,
             min_coins,
@@ -164,40 +615,80 @@ async fn main() {
             min_coin_amount,
             exclude_addresses,
             fee,
-            testnet11,
+            burn,
+            destination,
+            state_file,
+            resume,
+            report,
+            export,
+            signers,
+            dry_run,
         } => {
             cli_revoke_bulk(
-                launcher_id,
+                profile.launcher_id(launcher_id)?,
+                profile.nonce(nonce)?,
                 csv,
                 percentage,
                 min_coins,
                 max_coins,
                 min_coin_amount,
-                exclude_addresses,
-                fee,
-                testnet11,
+                profile.exclude_addresses(exclude_addresses),
+                profile.fee(fee, "0.0"),
+                burn,
+                profile.destination(destination, burn),
+                state_file,
+                resume,
+                report,
+                export,
+                signers,
+                dry_run,
+                network,
             )
             .await
         }
+        Commands::Vault { command } => match command {
+            VaultCommands::Info { launcher_id } => {
+                cli_vault_info(profile.launcher_id(launcher_id)?, network).await
+            }
+            VaultCommands::Rotate {
+                launcher_id,
+                m,
+                members,
+                fee,
+                export,
+                signers,
+                dry_run,
+            } => {
+                cli_vault_rotate(
+                    profile.launcher_id(launcher_id)?,
+                    m,
+                    members,
+                    profile.fee(fee, "0.0"),
+                    export,
+                    signers,
+                    dry_run,
+                    network,
+                )
+                .await
+            }
+        },
+        Commands::SignVaultSpend { file } => cli_sign_vault_spend(file).await,
+        Commands::SubmitVaultSpend { file } => cli_submit_vault_spend(file).await,
+        Commands::CreateKeyfile { file } => cli_create_keyfile(file).await,
         Commands::GenerateSendMessageBundle {
             launcher_id,
             message,
             receiver_puzzle_hash,
             output_file,
-            testnet11,
         } => {
             cli_generate_send_message_bundle(
-                launcher_id,
+                profile.launcher_id(launcher_id)?,
                 message,
                 receiver_puzzle_hash,
                 output_file,
-                testnet11,
+                network,
             )
             .await
         }
-    };
-
-    if let Err(err) = res {
-        eprintln!("Error: {err}");
     }
 }
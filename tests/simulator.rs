@@ -0,0 +1,519 @@
+use anyhow::Result;
+use chia::{
+    bls::{PublicKey, Signature, aggregate},
+    protocol::{Bytes32, CoinSpend, SpendBundle},
+};
+use chia_puzzle_types::cat::CatArgs;
+use chia_puzzles::SETTLEMENT_PAYMENT_HASH;
+use chia_wallet_sdk::{
+    driver::{Cat, CatSpend, MedievalVault, Puzzle, SpendContext, StandardLayer},
+    prelude::ToTreeHash,
+    test::{BlsPair, Simulator, sign_transaction},
+    types::{Conditions, Mod, puzzles::RevocationArgs},
+};
+use rcli::{
+    IssuanceOutput, Network, RevocationBatchStatus, RevocationTransactionReport, RevokeBulkState,
+    RevokedCoinReport, get_rcat_asset_id, get_rcat_hidden_puzzle_hash, get_revocation_cost,
+    get_revoked_amount, get_revoked_coin_report, spend_issuance, spend_revocation,
+    spend_vault_launch, split_revocation_batches,
+};
+
+const NONCE: u64 = 0;
+
+struct TestVault {
+    launcher_id: Bytes32,
+    vault: MedievalVault,
+    members: Vec<BlsPair>,
+}
+
+impl TestVault {
+    // Launches an m-of-n vault
+    fn launch(sim: &mut Simulator, m: usize, n: u64) -> Result<Self> {
+        let members = (0..n).map(BlsPair::new).collect::<Vec<_>>();
+        let funding_coin = sim.new_coin(SETTLEMENT_PAYMENT_HASH.into(), 1);
+
+        let mut ctx = SpendContext::new();
+        let (launcher_id, _, security_coin_sig) = spend_vault_launch(
+            &mut ctx,
+            funding_coin,
+            m,
+            members.iter().map(|member| member.pk).collect(),
+            &Network::testnet11(),
+        )?;
+
+        let coin_spends = ctx.take();
+        let launcher_spend = find_spend(&coin_spends, launcher_id);
+        sim.new_transaction(SpendBundle::new(coin_spends, security_coin_sig))?;
+
+        let vault = MedievalVault::from_launcher_spend(&mut ctx, launcher_spend)?.unwrap();
+
+        Ok(Self {
+            launcher_id,
+            vault,
+            members,
+        })
+    }
+
+    // The first m members sign all vault spends
+    fn signers(&self) -> Vec<PublicKey> {
+        self.members[..self.vault.info.m]
+            .iter()
+            .map(|member| member.pk)
+            .collect()
+    }
+
+    // Signs the vault spend in the bundle and moves on to the vault's next coin
+    fn sign(&mut self, coin_spends: &[CoinSpend]) -> Result<Signature> {
+        let vault_spend = find_spend(coin_spends, self.vault.coin.coin_id());
+        let secret_keys = self.members[..self.vault.info.m]
+            .iter()
+            .map(|member| member.sk.clone())
+            .collect::<Vec<_>>();
+        let signature = sign_transaction(std::slice::from_ref(&vault_spend), &secret_keys)?;
+
+        let mut ctx = SpendContext::new();
+        self.vault = MedievalVault::from_parent_spend(&mut ctx, vault_spend)?.unwrap();
+
+        Ok(signature)
+    }
+
+    fn issue(&mut self, sim: &mut Simulator, outputs: Vec<IssuanceOutput>) -> Result<Vec<Cat>> {
+        let amount = outputs.iter().map(|output| output.amount).sum::<u64>();
+        let funding_coin = sim.new_coin(SETTLEMENT_PAYMENT_HASH.into(), amount);
+
+        let mut ctx = SpendContext::new();
+        let (security_coin, security_coin_sig) = spend_issuance(
+            &mut ctx,
+            self.launcher_id,
+            NONCE,
+            &Network::testnet11(),
+            self.vault.clone(),
+            &self.signers(),
+            funding_coin,
+            outputs,
+        )?;
+
+        let coin_spends = ctx.take();
+        let vault_sig = self.sign(&coin_spends)?;
+
+        // The eve rCAT is the only coin created by the security coin
+        let eve_cat_spend = coin_spends
+            .iter()
+            .find(|coin_spend| coin_spend.coin.parent_coin_info == security_coin.coin_id())
+            .cloned()
+            .unwrap();
+        sim.new_transaction(SpendBundle::new(
+            coin_spends,
+            aggregate(&[security_coin_sig, vault_sig]),
+        ))?;
+
+        let puzzle = ctx.alloc(&eve_cat_spend.puzzle_reveal)?;
+        let puzzle = Puzzle::parse(&ctx, puzzle);
+        let solution = ctx.alloc(&eve_cat_spend.solution)?;
+
+        Ok(Cat::parse_children(&mut ctx, eve_cat_spend.coin, puzzle, solution)?.unwrap())
+    }
+
+    fn revoke(
+        &mut self,
+        sim: &mut Simulator,
+        cats: Vec<Cat>,
+        percentage: u8,
+        burn: bool,
+        target_ph: Bytes32,
+    ) -> Result<Vec<RevokedCoinReport>> {
+        let reports = cats
+            .iter()
+            .map(|cat| get_revoked_coin_report(self.launcher_id, NONCE, cat, percentage))
+            .collect::<Vec<_>>();
+
+        let mut ctx = SpendContext::new();
+        spend_revocation(
+            &mut ctx,
+            self.launcher_id,
+            NONCE,
+            &Network::testnet11(),
+            self.vault.clone(),
+            &self.signers(),
+            cats,
+            percentage,
+            burn,
+            target_ph,
+        )?;
+
+        let coin_spends = ctx.take();
+        let vault_sig = self.sign(&coin_spends)?;
+        sim.new_transaction(SpendBundle::new(coin_spends, vault_sig))?;
+
+        Ok(reports)
+    }
+
+    // Revokes a batch planned by a revoke-bulk run; returns the report the run saves
+    fn revoke_batch(
+        &mut self,
+        sim: &mut Simulator,
+        cats: Vec<Cat>,
+        state: &RevokeBulkState,
+    ) -> Result<RevocationTransactionReport> {
+        let coins = cats
+            .iter()
+            .map(|cat| get_revoked_coin_report(self.launcher_id, NONCE, cat, state.percentage))
+            .collect();
+        let vault_coin_id = self.vault.coin.coin_id();
+
+        let mut ctx = SpendContext::new();
+        spend_revocation(
+            &mut ctx,
+            self.launcher_id,
+            NONCE,
+            &state.network,
+            self.vault.clone(),
+            &self.signers(),
+            cats,
+            state.percentage,
+            state.burn,
+            state.destination,
+        )?;
+
+        let coin_spends = ctx.take();
+        let vault_sig = self.sign(&coin_spends)?;
+        let sb = SpendBundle::new(coin_spends, vault_sig);
+        let transaction_id = sb.name();
+        sim.new_transaction(sb)?;
+
+        Ok(RevocationTransactionReport {
+            transaction_id,
+            vault_coin_id,
+            fee: 0,
+            destination: if state.burn {
+                None
+            } else {
+                Some(state.destination)
+            },
+            coins,
+        })
+    }
+
+    fn balance(&self, sim: &Simulator, p2_puzzle_hash: Bytes32) -> u64 {
+        let puzzle_hash = CatArgs::curry_tree_hash(
+            get_rcat_asset_id(self.launcher_id, NONCE),
+            RevocationArgs::new(
+                get_rcat_hidden_puzzle_hash(self.launcher_id),
+                p2_puzzle_hash,
+            )
+            .curry_tree_hash(),
+        );
+
+        sim.unspent_coins(puzzle_hash.into(), false)
+            .iter()
+            .map(|coin| coin.amount)
+            .sum()
+    }
+}
+
+fn find_spend(coin_spends: &[CoinSpend], coin_id: Bytes32) -> CoinSpend {
+    coin_spends
+        .iter()
+        .find(|coin_spend| coin_spend.coin.coin_id() == coin_id)
+        .cloned()
+        .unwrap()
+}
+
+fn output(holder: &BlsPair, amount: u64) -> IssuanceOutput {
+    IssuanceOutput {
+        puzzle_hash: holder.puzzle_hash,
+        amount,
+        memo: None,
+    }
+}
+
+// Sends an rCAT to the given puzzle hashes using the owner's standard puzzle
+fn send_rcat(
+    sim: &mut Simulator,
+    cat: Cat,
+    owner: &BlsPair,
+    outputs: &[(Bytes32, u64)],
+) -> Result<Vec<Cat>> {
+    let mut ctx = SpendContext::new();
+
+    let mut conditions = Conditions::new();
+    for (puzzle_hash, amount) in outputs {
+        let hint = ctx.hint(*puzzle_hash)?;
+        conditions = conditions.create_coin(*puzzle_hash, *amount, hint);
+    }
+    let inner_spend = StandardLayer::new(owner.pk).spend_with_conditions(&mut ctx, conditions)?;
+    let _ = Cat::spend_all(&mut ctx, &[CatSpend::new(cat, inner_spend)])?;
+
+    sim.spend_coins(ctx.take(), std::slice::from_ref(&owner.sk))?;
+
+    Ok(outputs
+        .iter()
+        .map(|(puzzle_hash, amount)| cat.child(*puzzle_hash, *amount))
+        .collect())
+}
+
+fn is_unspent(sim: &Simulator, coin_id: Bytes32) -> bool {
+    sim.coin_state(coin_id)
+        .is_some_and(|coin_state| coin_state.spent_height.is_none())
+}
+
+#[test]
+fn test_launch_and_issue() -> Result<()> {
+    let mut sim = Simulator::new();
+    let mut vault = TestVault::launch(&mut sim, 2, 3)?;
+    assert!(is_unspent(&sim, vault.vault.coin.coin_id()));
+
+    let alice = BlsPair::new(10);
+    let bob = BlsPair::new(11);
+    let cats = vault.issue(&mut sim, vec![output(&alice, 1000), output(&bob, 500)])?;
+
+    assert_eq!(cats.len(), 2);
+    assert_eq!(vault.balance(&sim, alice.puzzle_hash), 1000);
+    assert_eq!(vault.balance(&sim, bob.puzzle_hash), 500);
+    assert!(is_unspent(&sim, vault.vault.coin.coin_id()));
+
+    // Issuing again spends the new vault coin
+    vault.issue(&mut sim, vec![output(&alice, 250)])?;
+    assert_eq!(vault.balance(&sim, alice.puzzle_hash), 1250);
+
+    Ok(())
+}
+
+#[test]
+fn test_move_between_holders() -> Result<()> {
+    let mut sim = Simulator::new();
+    let mut vault = TestVault::launch(&mut sim, 1, 1)?;
+
+    let alice = BlsPair::new(10);
+    let bob = BlsPair::new(11);
+    let cats = vault.issue(&mut sim, vec![output(&alice, 1000)])?;
+
+    let cats = send_rcat(
+        &mut sim,
+        cats[0],
+        &alice,
+        &[(bob.puzzle_hash, 300), (alice.puzzle_hash, 700)],
+    )?;
+    assert_eq!(vault.balance(&sim, alice.puzzle_hash), 700);
+    assert_eq!(vault.balance(&sim, bob.puzzle_hash), 300);
+
+    send_rcat(&mut sim, cats[0], &bob, &[(alice.puzzle_hash, 300)])?;
+    assert_eq!(vault.balance(&sim, alice.puzzle_hash), 1000);
+    assert_eq!(vault.balance(&sim, bob.puzzle_hash), 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_revoke_percentages() -> Result<()> {
+    for percentage in [0, 25, 50, 99, 100] {
+        let mut sim = Simulator::new();
+        let mut vault = TestVault::launch(&mut sim, 2, 3)?;
+
+        let alice = BlsPair::new(10);
+        let bob = BlsPair::new(11);
+        let carol = BlsPair::new(12);
+        let cats = vault.issue(&mut sim, vec![output(&alice, 1001), output(&bob, 333)])?;
+
+        // Bob's coin changes hands before being revoked
+        let cats = vec![
+            cats[0],
+            send_rcat(&mut sim, cats[1], &bob, &[(bob.puzzle_hash, 333)])?[0],
+        ];
+
+        let reports = vault.revoke(&mut sim, cats, percentage, false, carol.puzzle_hash)?;
+
+        let expected_revoked =
+            get_revoked_amount(1001, percentage) + get_revoked_amount(333, percentage);
+        assert_eq!(
+            reports.iter().map(|r| r.revoked_amount).sum::<u64>(),
+            expected_revoked
+        );
+        assert_eq!(vault.balance(&sim, carol.puzzle_hash), expected_revoked);
+        assert_eq!(
+            vault.balance(&sim, alice.puzzle_hash),
+            1001 * percentage as u64 / 100
+        );
+        assert_eq!(
+            vault.balance(&sim, bob.puzzle_hash),
+            333 * percentage as u64 / 100
+        );
+
+        for report in reports {
+            assert!(!is_unspent(&sim, report.coin_id));
+            match report.refund_coin_id {
+                Some(refund_coin_id) => assert!(is_unspent(&sim, refund_coin_id)),
+                None => assert_eq!(report.revoked_amount, report.original_amount),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_revoke_and_burn() -> Result<()> {
+    let mut sim = Simulator::new();
+    let mut vault = TestVault::launch(&mut sim, 2, 3)?;
+
+    let alice = BlsPair::new(10);
+    let bob = BlsPair::new(11);
+    let cats = vault.issue(&mut sim, vec![output(&alice, 1000), output(&bob, 400)])?;
+
+    let reports = vault.revoke(&mut sim, cats, 50, true, Bytes32::default())?;
+    assert_eq!(reports.iter().map(|r| r.revoked_amount).sum::<u64>(), 700);
+
+    // The revoked amount is melted; only the refunds remain
+    assert_eq!(vault.balance(&sim, alice.puzzle_hash), 500);
+    assert_eq!(vault.balance(&sim, bob.puzzle_hash), 200);
+
+    // The refunds are the only rCATs the revoked coins created, so the
+    //  supply went down by the revoked amount
+    let mut ctx = SpendContext::new();
+    let mut supply = 0;
+    for report in &reports {
+        let coin_spend = sim.coin_spend(report.coin_id).unwrap();
+        let puzzle = ctx.alloc(&coin_spend.puzzle_reveal)?;
+        let puzzle = Puzzle::parse(&ctx, puzzle);
+        let solution = ctx.alloc(&coin_spend.solution)?;
+        let children = Cat::parse_children(&mut ctx, coin_spend.coin, puzzle, solution)?.unwrap();
+
+        assert_eq!(
+            children
+                .iter()
+                .map(|child| child.coin.coin_id())
+                .collect::<Vec<_>>(),
+            report.refund_coin_id.into_iter().collect::<Vec<_>>()
+        );
+        supply += children
+            .iter()
+            .filter(|child| is_unspent(&sim, child.coin.coin_id()))
+            .map(|child| child.coin.amount)
+            .sum::<u64>();
+    }
+    assert_eq!(supply, 1400 - 700);
+
+    Ok(())
+}
+
+#[test]
+fn test_revoke_bulk_resume_keeps_report() -> Result<()> {
+    let mut sim = Simulator::new();
+    let mut vault = TestVault::launch(&mut sim, 2, 3)?;
+    let network = Network::testnet11();
+    let asset_id = get_rcat_asset_id(vault.launcher_id, NONCE);
+
+    // Same amounts, so all rCAT spends cost the same
+    let holders = (10..15).map(BlsPair::new).collect::<Vec<_>>();
+    let carol = BlsPair::new(20);
+    let cats = vault.issue(
+        &mut sim,
+        holders.iter().map(|holder| output(holder, 1000)).collect(),
+    )?;
+
+    let mut state = RevokeBulkState {
+        launcher_id: vault.launcher_id,
+        nonce: NONCE,
+        network: network.clone(),
+        percentage: 50,
+        burn: false,
+        destination: carol.puzzle_hash,
+        batches: vec![],
+    };
+
+    // Batches hold at most two coins
+    let max_cost = get_revocation_cost(
+        vault.launcher_id,
+        NONCE,
+        &network,
+        &vault.vault,
+        &vault.signers(),
+        &cats[..2],
+        state.percentage,
+        state.burn,
+        state.destination,
+    )?;
+    let batches = split_revocation_batches(
+        vault.launcher_id,
+        NONCE,
+        &network,
+        &vault.vault,
+        &vault.signers(),
+        cats.clone(),
+        state.percentage,
+        state.burn,
+        state.destination,
+        max_cost,
+    )?;
+    assert_eq!(
+        batches.iter().map(Vec::len).collect::<Vec<_>>(),
+        vec![2, 2, 1]
+    );
+    state.plan_batches(&batches);
+
+    // The first run confirms one batch and gets interrupted right after
+    //  submitting the second one
+    let first_report = vault.revoke_batch(&mut sim, batches[0].clone(), &state)?;
+    state.set_submitted(0, &first_report);
+    state.set_status(0, RevocationBatchStatus::Confirmed, None);
+    let second_report = vault.revoke_batch(&mut sim, batches[1].clone(), &state)?;
+    state.set_submitted(1, &second_report);
+
+    let state_file = std::env::temp_dir().join("rcli-test-revoke-bulk-state.json");
+    let state_file = state_file.to_str().unwrap();
+    state.save(state_file)?;
+
+    // The resumed run finds the second batch confirmed and plans the rest again
+    let mut state = RevokeBulkState::load(state_file)?;
+    std::fs::remove_file(state_file)?;
+    let unspent_coin_ids = state
+        .batches
+        .iter()
+        .flat_map(|batch| batch.coin_ids.clone())
+        .filter(|coin_id| is_unspent(&sim, *coin_id))
+        .collect::<Vec<_>>();
+    state.resume(&unspent_coin_ids);
+    assert_eq!(state.batches.len(), 2);
+    assert!(
+        state
+            .batches
+            .iter()
+            .all(|batch| batch.status == RevocationBatchStatus::Confirmed)
+    );
+
+    let remaining = cats
+        .into_iter()
+        .filter(|cat| unspent_coin_ids.contains(&cat.coin.coin_id()))
+        .collect::<Vec<_>>();
+    assert_eq!(remaining.len(), 1);
+    let batch_index = state.plan_batches(&[remaining.clone()]);
+    let third_report = vault.revoke_batch(&mut sim, remaining, &state)?;
+    state.set_submitted(batch_index, &third_report);
+    state.set_status(batch_index, RevocationBatchStatus::Confirmed, None);
+
+    let report = state.revocation_report(asset_id);
+    assert_eq!(
+        report
+            .transactions
+            .iter()
+            .map(|transaction| transaction.transaction_id)
+            .collect::<Vec<_>>(),
+        vec![
+            first_report.transaction_id,
+            second_report.transaction_id,
+            third_report.transaction_id
+        ]
+    );
+    assert_eq!(
+        report
+            .transactions
+            .iter()
+            .flat_map(|transaction| transaction.coins.iter())
+            .map(|coin| coin.revoked_amount)
+            .sum::<u64>(),
+        2500
+    );
+    assert_eq!(vault.balance(&sim, carol.puzzle_hash), 2500);
+
+    Ok(())
+}